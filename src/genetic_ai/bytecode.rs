@@ -0,0 +1,255 @@
+use rand::Rng;
+
+use super::{InternalNode, LeafNode, Node, NodeType, Tree};
+use crate::gym::game::Game;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Constant(f32),
+    //the program's nth feature, see `Program::features`
+    Feature(usize),
+    //a leaf that has to be evaluated every time it is reached
+    Leaf(LeafNode),
+    Apply(InternalNode),
+}
+
+//a tree flattened into postfix order for a stack machine. operands are pushed left
+//before right so `Random` leaves draw in the same order as `Agent::eval_recurse`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    //the distinct state dependent leaves, computed once per evaluation however often
+    //they appear
+    pub features: Vec<LeafNode>,
+    //deepest the stack gets while running
+    pub stack_size: usize,
+}
+
+impl Tree {
+    pub fn compile(&self) -> Program {
+        let mut program = Program {
+            instructions: Vec::with_capacity(self.size()),
+            features: Vec::new(),
+            stack_size: 0,
+        };
+        program.stack_size = emit(&self.root, &mut program);
+        program
+    }
+}
+
+//returns the stack depth needed to evaluate `node`
+fn emit(node: &Node, program: &mut Program) -> usize {
+    match &node.node_type {
+        NodeType::Leaf(LeafNode::Constant(value)) => {
+            program.instructions.push(Instruction::Constant(*value));
+            1
+        }
+        NodeType::Leaf(LeafNode::Random) => {
+            program
+                .instructions
+                .push(Instruction::Leaf(LeafNode::Random));
+            1
+        }
+        NodeType::Leaf(leaf) => {
+            let index = match program.features.iter().position(|f| f == leaf) {
+                Some(index) => index,
+                None => {
+                    program.features.push(*leaf);
+                    program.features.len() - 1
+                }
+            };
+            program.instructions.push(Instruction::Feature(index));
+            1
+        }
+        NodeType::Internal(internal) => {
+            //each argument sits on top of the ones before it
+            let depth = node
+                .children
+                .iter()
+                .enumerate()
+                .map(|(position, child)| position + emit(child, program))
+                .max()
+                .unwrap_or(1);
+            program.instructions.push(Instruction::Apply(*internal));
+            depth
+        }
+    }
+}
+
+impl Program {
+    //big enough for any evaluation of this program without reallocating
+    pub fn buffer(&self) -> Vec<f32> {
+        Vec::with_capacity(self.features.len() + self.stack_size)
+    }
+    pub fn eval<R: Rng + ?Sized>(&self, state: &Game, rng: &mut R) -> f32 {
+        self.eval_with(state, rng, &mut self.buffer())
+    }
+    //reuses `buffer` between calls to avoid allocating
+    pub fn eval_with<R: Rng + ?Sized>(
+        &self,
+        state: &Game,
+        rng: &mut R,
+        buffer: &mut Vec<f32>,
+    ) -> f32 {
+        buffer.clear();
+        buffer.reserve(self.features.len() + self.stack_size);
+        //features sit at the bottom of the buffer with the stack above them
+        for feature in &self.features {
+            buffer.push(feature.get_value(state, rng));
+        }
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Constant(value) => buffer.push(*value),
+                Instruction::Feature(index) => buffer.push(buffer[*index]),
+                Instruction::Leaf(leaf) => buffer.push(leaf.get_value(state, rng)),
+                Instruction::Apply(internal) => {
+                    let args = buffer.len() - internal.arity();
+                    let value = internal.eval(&buffer[args..], state);
+                    buffer.truncate(args);
+                    buffer.push(value);
+                }
+            }
+        }
+        buffer.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        genetic_ai::{Agent, Method},
+        gym::{game::GameConfig, snake::Direction},
+    };
+
+    fn states() -> Vec<Game> {
+        let mut states = Vec::new();
+        for seed in 0..5 {
+            let mut game = Game::with_config(GameConfig::new().seed(seed));
+            game.reset();
+            states.extend(game.get_possible_states().into_iter().map(|(_, s)| s));
+        }
+        states
+    }
+
+    #[test]
+    fn postfix_order_and_stack_size() {
+        let program = Tree::from_sexpr("(Sub Row (Add Column (Mul (Max Random 2) Row)))")
+            .unwrap()
+            .compile();
+        assert_eq!(
+            program.instructions,
+            vec![
+                Instruction::Feature(0),
+                Instruction::Feature(1),
+                Instruction::Leaf(LeafNode::Random),
+                Instruction::Constant(2.0),
+                Instruction::Apply(InternalNode::Max),
+                Instruction::Feature(0),
+                Instruction::Apply(InternalNode::Mul),
+                Instruction::Apply(InternalNode::Add),
+                Instruction::Apply(InternalNode::Sub),
+            ]
+        );
+        assert_eq!(program.features, vec![LeafNode::Row, LeafNode::Column]);
+        assert_eq!(program.stack_size, 4);
+    }
+
+    #[test]
+    fn conditional_and_unary_primitives() {
+        //heading up from the top row, so straight on is the wall
+        let config = GameConfig::new()
+            .starting_position(2, 0)
+            .starting_direction(Direction::Up);
+        let game = Game::with_config(config);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for (text, expected) in [
+            ("(IfLessThan Row Column 7 8)", 7.0),
+            ("(IfLessThan Column Row 7 8)", 8.0),
+            ("(IfDanger 1 (Neg 1))", 1.0),
+            ("(Sqrt (Neg 9))", 3.0),
+            ("(Abs (Neg Column))", 2.0),
+            ("(Sigmoid 0)", 0.5),
+        ] {
+            let agent = Agent::from_gene(Tree::from_sexpr(text).unwrap());
+            assert_eq!(agent.evaluate(&game, &mut rng), expected, "{}", text);
+            assert_eq!(agent.gene.compile().eval(&game, &mut rng), expected);
+        }
+    }
+
+    #[test]
+    fn matches_the_recursive_evaluator() {
+        let states = states();
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        for index in 0..200 {
+            let method = if index % 2 == 0 {
+                Method::Grow
+            } else {
+                Method::Full
+            };
+            let agent = Agent::new(6, method, &mut rng);
+            let program = agent.gene.compile();
+            let mut first = ChaCha8Rng::seed_from_u64(index);
+            let mut second = ChaCha8Rng::seed_from_u64(index);
+            let mut buffer = program.buffer();
+            let capacity = buffer.capacity();
+            for state in &states {
+                let expected = agent.evaluate(state, &mut first);
+                let actual = program.eval_with(state, &mut second, &mut buffer);
+                assert_eq!(expected.to_bits(), actual.to_bits());
+            }
+            //`stack_size` was enough that the buffer never had to grow
+            assert_eq!(buffer.capacity(), capacity);
+        }
+    }
+
+    //cargo test --release bytecode_speedup -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bytecode_speedup() {
+        let states = states();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let agents: Vec<Agent> = (0..100)
+            .map(|_| Agent::new(8, Method::Full, &mut rng))
+            .collect();
+        let programs: Vec<Program> = agents.iter().map(|a| a.gene.compile()).collect();
+        let rounds = 200;
+
+        let start = Instant::now();
+        let mut recursive = 0.0;
+        for _ in 0..rounds {
+            for agent in &agents {
+                for state in &states {
+                    recursive += agent.evaluate(state, &mut rng);
+                }
+            }
+        }
+        let recursive_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut compiled = 0.0;
+        let mut buffer = Vec::new();
+        for _ in 0..rounds {
+            for program in &programs {
+                for state in &states {
+                    compiled += program.eval_with(state, &mut rng, &mut buffer);
+                }
+            }
+        }
+        let compiled_time = start.elapsed();
+
+        println!(
+            "recursive {:?} bytecode {:?} speedup {:.2}x ({} {})",
+            recursive_time,
+            compiled_time,
+            recursive_time.as_secs_f64() / compiled_time.as_secs_f64(),
+            recursive,
+            compiled
+        );
+    }
+}
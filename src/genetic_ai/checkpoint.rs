@@ -0,0 +1,251 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    fitness::{FitnessFunction, FitnessMethod},
+    population::{EvolutionConfig, GenerationStats, Population},
+};
+use crate::gym::game::GameConfig;
+
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct CheckpointConfig {
+    pub path: PathBuf,
+    //save after every this many evaluated generations, and once more at the end
+    pub every: u32,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    //the run used a custom fitness function, see `Run::load_with_fitness`
+    CustomFitness,
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(error) => write!(f, "could not read checkpoint: {}", error),
+            CheckpointError::Format(error) => write!(f, "malformed checkpoint: {}", error),
+            CheckpointError::Version(version) => write!(
+                f,
+                "checkpoint version {} is not supported, expected {}",
+                version, CHECKPOINT_VERSION
+            ),
+            CheckpointError::CustomFitness => write!(
+                f,
+                "checkpoint used a custom fitness function, which has to be supplied again"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(error: serde_json::Error) -> Self {
+        CheckpointError::Format(error)
+    }
+}
+
+//everything an evolutionary run needs to carry on where it stopped: the population
+//with its rng and generation counter, the configs and the stats so far
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub version: u32,
+    pub game: GameConfig,
+    pub config: EvolutionConfig,
+    pub generations: u32,
+    pub population: Population,
+    pub history: Vec<GenerationStats>,
+}
+
+impl Run {
+    pub fn new(
+        population: Population,
+        game: GameConfig,
+        config: EvolutionConfig,
+        generations: u32,
+    ) -> Run {
+        Run {
+            version: CHECKPOINT_VERSION,
+            game,
+            config,
+            generations,
+            population,
+            history: Vec::new(),
+        }
+    }
+    //the renderer is not saved, set it on `config.evaluation` again if it is wanted
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Run, CheckpointError> {
+        let run = Run::read(path)?;
+        if run.config.evaluation.fitness.is_custom() {
+            return Err(CheckpointError::CustomFitness);
+        }
+        Ok(run)
+    }
+    //for a run that used a custom fitness function, which has to be the same one for
+    //the run to continue as it would have
+    pub fn load_with_fitness<P: AsRef<Path>, F: FitnessFunction + Send + Sync + 'static>(
+        path: P,
+        function: F,
+    ) -> Result<Run, CheckpointError> {
+        let mut run = Run::read(path)?;
+        run.config.evaluation.fitness = FitnessMethod::custom(function);
+        Ok(run)
+    }
+    fn read<P: AsRef<Path>>(path: P) -> Result<Run, CheckpointError> {
+        let run: Run = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if run.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::Version(run.version));
+        }
+        Ok(run)
+    }
+    //written beside the old checkpoint and renamed over it, so being killed mid write
+    //leaves the previous one intact
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        fs::write(&partial, serde_json::to_string(self)?)?;
+        fs::rename(partial, path)
+    }
+    pub fn is_finished(&self) -> bool {
+        self.history.len() >= self.generations as usize
+    }
+    //evaluate the next generation, breeding it first if the current one has already
+    //been evaluated
+    pub fn step(&mut self) -> &GenerationStats {
+        let evaluated = self
+            .history
+            .last()
+            .is_some_and(|stats| stats.generation == self.population.generation);
+        if evaluated {
+            self.population.next_generation(&self.config);
+        }
+        self.population
+            .evaluate(&self.game, &self.config.evaluation);
+        self.history
+            .push(self.population.stats(self.population.generation));
+        self.history.last().unwrap()
+    }
+    pub fn evolve(&mut self, checkpoint: Option<&CheckpointConfig>) -> io::Result<()> {
+        while !self.is_finished() {
+            self.step();
+            if let Some(checkpoint) = checkpoint {
+                let every = checkpoint.every.max(1) as usize;
+                if self.history.len().is_multiple_of(every) || self.is_finished() {
+                    self.save(&checkpoint.path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_ai::{
+        evaluation::Episode,
+        fitness::SurvivalBonus,
+        selection::{Lexicase, SelectionMethod},
+    };
+
+    fn run() -> Run {
+        let config = EvolutionConfig {
+            selection: SelectionMethod::Lexicase(Lexicase { epsilon: None }),
+            ..EvolutionConfig::default()
+        };
+        let game = GameConfig::new().size(6, 6).step_limit(150);
+        Run::new(Population::new(8, 3, 11), game, config, 6)
+    }
+
+    fn snapshot(run: &Run) -> Vec<(String, f32, Vec<f32>)> {
+        run.population
+            .individuals
+            .iter()
+            .map(|a| (a.gene.to_sexpr(), a.fitness, a.cases.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn resumed_run_matches_uninterrupted_run() {
+        let mut uninterrupted = run();
+        uninterrupted.evolve(None).unwrap();
+
+        let path = std::env::temp_dir().join("snake-checkpoint-resume.json");
+        let mut interrupted = run();
+        for _ in 0..3 {
+            interrupted.step();
+        }
+        interrupted.save(&path).unwrap();
+        drop(interrupted);
+
+        let mut resumed = Run::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.history.len(), 3);
+        resumed.evolve(None).unwrap();
+
+        assert_eq!(resumed.history, uninterrupted.history);
+        assert_eq!(snapshot(&resumed), snapshot(&uninterrupted));
+        assert_eq!(resumed.population.rng, uninterrupted.population.rng);
+        assert_eq!(resumed.population.generation, 5);
+    }
+
+    #[test]
+    fn fitness_function_is_restored_or_refused() {
+        let path = std::env::temp_dir().join("snake-checkpoint-fitness.json");
+        let mut run = run();
+        run.config.evaluation.fitness =
+            FitnessMethod::SurvivalBonus(SurvivalBonus { per_step: 0.1 });
+        run.save(&path).unwrap();
+        let loaded = Run::load(&path).unwrap();
+        assert!(matches!(
+            loaded.config.evaluation.fitness,
+            FitnessMethod::SurvivalBonus(SurvivalBonus { per_step }) if per_step == 0.1
+        ));
+
+        run.config.evaluation.fitness = FitnessMethod::custom(|e: &Episode| e.steps as f32);
+        run.save(&path).unwrap();
+        assert!(matches!(
+            Run::load(&path),
+            Err(CheckpointError::CustomFitness)
+        ));
+        let mut loaded = Run::load_with_fitness(&path, |e: &Episode| e.steps as f32).unwrap();
+        fs::remove_file(&path).unwrap();
+        loaded.step();
+        for individual in &loaded.population.individuals {
+            assert_eq!(individual.fitness, individual.stats().steps.mean);
+        }
+    }
+
+    #[test]
+    fn saves_every_n_generations() {
+        let path = std::env::temp_dir().join("snake-checkpoint-every.json");
+        let mut run = run();
+        run.generations = 5;
+        run.evolve(Some(&CheckpointConfig {
+            path: path.clone(),
+            every: 2,
+        }))
+        .unwrap();
+        let saved = Run::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(saved.is_finished());
+        assert_eq!(saved.history, run.history);
+    }
+}
@@ -0,0 +1,149 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::Tree;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossoverConfig {
+    //chance of cutting at an internal node instead of a leaf
+    pub internal_bias: f32,
+    pub max_depth: u32,
+    pub max_size: usize,
+}
+
+impl Default for CrossoverConfig {
+    fn default() -> Self {
+        CrossoverConfig {
+            internal_bias: 0.9,
+            max_depth: 17,
+            max_size: 512,
+        }
+    }
+}
+
+impl CrossoverConfig {
+    fn allows(&self, tree: &Tree) -> bool {
+        tree.depth() <= self.max_depth && tree.size() <= self.max_size
+    }
+}
+
+pub fn crossover_point<R: Rng + ?Sized>(tree: &Tree, internal_bias: f32, rng: &mut R) -> usize {
+    let nodes = tree.nodes();
+    let (leaves, internals): (Vec<usize>, Vec<usize>) =
+        (0..nodes.len()).partition(|index| nodes[*index].is_leaf());
+    let candidates = if !internals.is_empty() && rng.gen::<f32>() < internal_bias {
+        internals
+    } else {
+        leaves
+    };
+    candidates[rng.gen_range(0..candidates.len())]
+}
+
+//swap randomly chosen subtrees between two parents, falling back to a copy of the
+//parent for any child that breaks the depth or size limit
+pub fn crossover<R: Rng + ?Sized>(
+    first: &Tree,
+    second: &Tree,
+    config: &CrossoverConfig,
+    rng: &mut R,
+) -> (Tree, Tree) {
+    let first_point = crossover_point(first, config.internal_bias, rng);
+    let second_point = crossover_point(second, config.internal_bias, rng);
+    swap_subtrees(first, first_point, second, second_point, config)
+}
+
+pub fn swap_subtrees(
+    first: &Tree,
+    first_point: usize,
+    second: &Tree,
+    second_point: usize,
+    config: &CrossoverConfig,
+) -> (Tree, Tree) {
+    let mut first_child = first.clone();
+    *first_child.subtree_mut(first_point) = second.subtree(second_point).clone();
+    let mut second_child = second.clone();
+    *second_child.subtree_mut(second_point) = first.subtree(first_point).clone();
+
+    if !config.allows(&first_child) {
+        first_child = first.clone();
+    }
+    if !config.allows(&second_child) {
+        second_child = second.clone();
+    }
+    (first_child, second_child)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::genetic_ai::{InternalNode, LeafNode, NodeType};
+
+    fn small_tree() -> Tree {
+        Tree::from_sexpr("(Sub Random AppleDistance)").unwrap()
+    }
+
+    fn larger_tree() -> Tree {
+        Tree::from_sexpr("(Add (Mul Row Column) SnakeLength)").unwrap()
+    }
+
+    #[test]
+    fn swaps_the_chosen_subtrees() {
+        let config = CrossoverConfig::default();
+        //cut the small tree at AppleDistance and the larger one at (Mul Row Column)
+        let (first, second) = swap_subtrees(&small_tree(), 2, &larger_tree(), 1, &config);
+
+        assert_eq!(first.size(), 5);
+        assert_eq!(first.depth(), 2);
+        assert_eq!(
+            first.subtree(2).node_type,
+            NodeType::Internal(InternalNode::Mul)
+        );
+        assert_eq!(second.size(), 3);
+        assert_eq!(
+            second.subtree(1).node_type,
+            NodeType::Leaf(LeafNode::AppleDistance)
+        );
+        assert_eq!(
+            second.subtree(2).node_type,
+            NodeType::Leaf(LeafNode::SnakeLength)
+        );
+    }
+
+    #[test]
+    fn children_over_the_limit_fall_back_to_parents() {
+        let config = CrossoverConfig {
+            max_depth: 1,
+            ..CrossoverConfig::default()
+        };
+        let (first, second) = swap_subtrees(&small_tree(), 2, &larger_tree(), 1, &config);
+        assert_eq!(first.to_string(), small_tree().to_string());
+        assert_eq!(second.size(), 3);
+    }
+
+    #[test]
+    fn random_crossover_preserves_material() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let config = CrossoverConfig::default();
+        for _ in 0..100 {
+            let (first, second) = crossover(&small_tree(), &larger_tree(), &config, &mut rng);
+            assert_eq!(first.size() + second.size(), 8);
+        }
+    }
+
+    #[test]
+    fn bias_controls_cut_point() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let tree = larger_tree();
+        for _ in 0..100 {
+            assert!(!tree
+                .subtree(crossover_point(&tree, 1.0, &mut rng))
+                .is_leaf());
+            assert!(tree
+                .subtree(crossover_point(&tree, 0.0, &mut rng))
+                .is_leaf());
+        }
+    }
+}
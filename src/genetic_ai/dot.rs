@@ -0,0 +1,108 @@
+use rand::Rng;
+
+use super::{InternalNode, Node, NodeType, Tree};
+use crate::gym::game::Game;
+
+impl Tree {
+    //graphviz source with conditionals as diamonds, other internal nodes as ellipses
+    //and leaves as boxes, nodes are named after their preorder index
+    pub fn to_dot(&self) -> String {
+        self.dot(None)
+    }
+    //same as `to_dot` but every node is also labelled with what it evaluates to in `state`
+    pub fn to_dot_evaluated<R: Rng + ?Sized>(&self, state: &Game, rng: &mut R) -> String {
+        let mut values = vec![0.0; self.size()];
+        evaluate(&self.root, state, rng, &mut 0, &mut values);
+        self.dot(Some(&values))
+    }
+    fn dot(&self, values: Option<&[f32]>) -> String {
+        let mut out = String::from("digraph tree {\n    node [fontname=\"monospace\"];\n");
+        for (index, node) in self.nodes().into_iter().enumerate() {
+            let shape = match node.node_type {
+                NodeType::Internal(InternalNode::IfLessThan | InternalNode::IfDanger) => "diamond",
+                NodeType::Internal(_) => "ellipse",
+                NodeType::Leaf(_) => "box",
+            };
+            let label = match values {
+                Some(values) => format!("{}\\n{:.2}", node, values[index]),
+                None => node.to_string(),
+            };
+            out.push_str(&format!(
+                "    n{} [label=\"{}\", shape={}];\n",
+                index, label, shape
+            ));
+        }
+        edges(&self.root, &mut 0, &mut out);
+        out.push_str("}\n");
+        out
+    }
+}
+
+//writes the edges below `node` and returns its index
+fn edges(node: &Node, next: &mut usize, out: &mut String) -> usize {
+    let index = *next;
+    *next += 1;
+    for child in node.children() {
+        let child = edges(child, next, out);
+        out.push_str(&format!("    n{} -> n{};\n", index, child));
+    }
+    index
+}
+
+//fills `values` in preorder, evaluating children left to right like `Agent::eval_recurse`
+fn evaluate<R: Rng + ?Sized>(
+    node: &Node,
+    state: &Game,
+    rng: &mut R,
+    next: &mut usize,
+    values: &mut [f32],
+) -> f32 {
+    let index = *next;
+    *next += 1;
+    let value = match &node.node_type {
+        NodeType::Leaf(leaf) => leaf.get_value(state, rng),
+        NodeType::Internal(internal) => {
+            let args: Vec<f32> = node
+                .children
+                .iter()
+                .map(|child| evaluate(child, state, rng, next, values))
+                .collect();
+            internal.eval(&args, state)
+        }
+    };
+    values[index] = value;
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::gym::game::GameConfig;
+
+    #[test]
+    fn shapes_and_edges() {
+        let tree = Tree::from_sexpr("(Max (Div Row Column) Random)").unwrap();
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph tree {"));
+        assert!(dot.contains("n0 [label=\"Max\", shape=ellipse];"));
+        assert!(dot.contains("n1 [label=\"Div\", shape=ellipse];"));
+        assert!(dot.contains("n4 [label=\"Random\", shape=box];"));
+        for edge in ["n0 -> n1;", "n1 -> n2;", "n1 -> n3;", "n0 -> n4;"] {
+            assert!(dot.contains(edge), "missing {}", edge);
+        }
+        assert_eq!(dot.matches("->").count(), 4);
+    }
+
+    #[test]
+    fn labels_values_for_a_state() {
+        let game = Game::with_config(GameConfig::new().starting_position(2, 3));
+        let tree = Tree::from_sexpr("(Sub Row Column)").unwrap();
+        let dot = tree.to_dot_evaluated(&game, &mut ChaCha8Rng::seed_from_u64(0));
+        assert!(dot.contains("n0 [label=\"Sub\\n1.00\", shape=ellipse];"));
+        assert!(dot.contains("n1 [label=\"Row\\n3.00\", shape=box];"));
+        assert!(dot.contains("n2 [label=\"Column\\n2.00\", shape=box];"));
+    }
+}
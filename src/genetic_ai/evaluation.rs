@@ -0,0 +1,144 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::gym::{
+    environment::{Environment, Policy},
+    game::Game,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Collision,
+    Starvation,
+    StepLimit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Episode {
+    pub seed: u64,
+    pub score: u32,
+    pub steps: u32,
+    pub apples: u32,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+    pub variance: f32,
+}
+
+impl Summary {
+    pub fn new(values: &[f32]) -> Summary {
+        if values.is_empty() {
+            return Summary {
+                mean: 0.0,
+                min: 0.0,
+                max: 0.0,
+                variance: 0.0,
+            };
+        }
+        let count = values.len() as f32;
+        let mean = values.iter().sum::<f32>() / count;
+        Summary {
+            mean,
+            min: values.iter().copied().fold(f32::INFINITY, f32::min),
+            max: values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+            variance: values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / count,
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mean {:.2} min {} max {} var {:.2}",
+            self.mean, self.min, self.max, self.variance
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvaluationStats {
+    pub score: Summary,
+    pub steps: Summary,
+    pub apples: Summary,
+}
+
+impl EvaluationStats {
+    pub fn new(episodes: &[Episode]) -> EvaluationStats {
+        let collect = |value: fn(&Episode) -> u32| {
+            Summary::new(&episodes.iter().map(|e| value(e) as f32).collect::<Vec<_>>())
+        };
+        EvaluationStats {
+            score: collect(|e| e.score),
+            steps: collect(|e| e.steps),
+            apples: collect(|e| e.apples),
+        }
+    }
+}
+
+//play one game from a reset using the given seed, stopping early if the snake goes
+//`starvation_limit` steps without eating
+pub fn play_episode<P: Policy<Game>>(
+    game: &mut Game,
+    policy: &mut P,
+    seed: u64,
+    starvation_limit: u32,
+) -> Episode {
+    game.reseed(seed);
+    game.reset();
+    let mut apples = 0;
+    let mut count = 0;
+    let mut starved = false;
+
+    while !game.lost {
+        if count > starvation_limit {
+            starved = true;
+            break;
+        }
+
+        let action = policy.act(game);
+        let result = game.step(action);
+
+        if result.info.ate {
+            apples += 1;
+            count = 0;
+        } else {
+            count += 1;
+        }
+    }
+
+    let outcome = if starved {
+        Outcome::Starvation
+    } else if game.config.step_limit == Some(game.steps) {
+        Outcome::StepLimit
+    } else {
+        Outcome::Collision
+    };
+    Episode {
+        seed,
+        score: game.score,
+        steps: game.steps,
+        apples,
+        outcome,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_scores() {
+        let summary = Summary::new(&[5.0, 15.0, 10.0, 10.0]);
+        assert_eq!(summary.mean, 10.0);
+        assert_eq!(summary.min, 5.0);
+        assert_eq!(summary.max, 15.0);
+        assert_eq!(summary.variance, 12.5);
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::evaluation::{Episode, Outcome};
+
+pub trait FitnessFunction {
+    fn fitness(&self, episode: &Episode) -> f32;
+}
+
+impl<F: Fn(&Episode) -> f32> FitnessFunction for F {
+    fn fitness(&self, episode: &Episode) -> f32 {
+        self(episode)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreOnly;
+
+impl FitnessFunction for ScoreOnly {
+    fn fitness(&self, episode: &Episode) -> f32 {
+        episode.score as f32
+    }
+}
+
+//score plus a reward for every step survived
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SurvivalBonus {
+    pub per_step: f32,
+}
+
+impl FitnessFunction for SurvivalBonus {
+    fn fitness(&self, episode: &Episode) -> f32 {
+        episode.score as f32 + self.per_step * episode.steps as f32
+    }
+}
+
+//apples eaten per step taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Efficiency;
+
+impl FitnessFunction for Efficiency {
+    fn fitness(&self, episode: &Episode) -> f32 {
+        episode.apples as f32 / episode.steps.max(1) as f32
+    }
+}
+
+//score minus a penalty depending on how the episode ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeathPenalty {
+    pub collision: f32,
+    pub starvation: f32,
+}
+
+impl FitnessFunction for DeathPenalty {
+    fn fitness(&self, episode: &Episode) -> f32 {
+        let penalty = match episode.outcome {
+            Outcome::Collision => self.collision,
+            Outcome::Starvation => self.starvation,
+            Outcome::StepLimit => 0.0,
+        };
+        episode.score as f32 - penalty
+    }
+}
+
+//the built in functions by name so they can be saved with a run. a custom function
+//cannot, it is saved as a placeholder that has to be replaced after loading
+#[derive(Clone, Serialize, Deserialize)]
+pub enum FitnessMethod {
+    ScoreOnly(ScoreOnly),
+    SurvivalBonus(SurvivalBonus),
+    Efficiency(Efficiency),
+    DeathPenalty(DeathPenalty),
+    Custom(#[serde(skip)] Option<Arc<dyn FitnessFunction + Send + Sync>>),
+}
+
+impl FitnessMethod {
+    pub fn custom<F: FitnessFunction + Send + Sync + 'static>(function: F) -> FitnessMethod {
+        FitnessMethod::Custom(Some(Arc::new(function)))
+    }
+    pub fn is_custom(&self) -> bool {
+        matches!(self, FitnessMethod::Custom(_))
+    }
+}
+
+impl Default for FitnessMethod {
+    fn default() -> Self {
+        FitnessMethod::ScoreOnly(ScoreOnly)
+    }
+}
+
+impl FitnessFunction for FitnessMethod {
+    fn fitness(&self, episode: &Episode) -> f32 {
+        match self {
+            FitnessMethod::ScoreOnly(method) => method.fitness(episode),
+            FitnessMethod::SurvivalBonus(method) => method.fitness(episode),
+            FitnessMethod::Efficiency(method) => method.fitness(episode),
+            FitnessMethod::DeathPenalty(method) => method.fitness(episode),
+            FitnessMethod::Custom(Some(method)) => method.fitness(episode),
+            FitnessMethod::Custom(None) => {
+                panic!("custom fitness function was not restored after loading")
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for FitnessMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitnessMethod::ScoreOnly(method) => write!(f, "{:?}", method),
+            FitnessMethod::SurvivalBonus(method) => write!(f, "{:?}", method),
+            FitnessMethod::Efficiency(method) => write!(f, "{:?}", method),
+            FitnessMethod::DeathPenalty(method) => write!(f, "{:?}", method),
+            FitnessMethod::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(outcome: Outcome) -> Episode {
+        Episode {
+            seed: 0,
+            score: 20,
+            steps: 40,
+            apples: 4,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn built_in_fitness_functions() {
+        let collided = episode(Outcome::Collision);
+        assert_eq!(ScoreOnly.fitness(&collided), 20.0);
+        assert_eq!(SurvivalBonus { per_step: 0.5 }.fitness(&collided), 40.0);
+        assert_eq!(Efficiency.fitness(&collided), 0.1);
+
+        let penalty = DeathPenalty {
+            collision: 10.0,
+            starvation: 3.0,
+        };
+        assert_eq!(penalty.fitness(&collided), 10.0);
+        assert_eq!(penalty.fitness(&episode(Outcome::Starvation)), 17.0);
+        assert_eq!(penalty.fitness(&episode(Outcome::StepLimit)), 20.0);
+    }
+
+    #[test]
+    fn built_in_methods_survive_serialization() {
+        let method = FitnessMethod::DeathPenalty(DeathPenalty {
+            collision: 10.0,
+            starvation: 3.0,
+        });
+        let loaded: FitnessMethod =
+            serde_json::from_str(&serde_json::to_string(&method).unwrap()).unwrap();
+        assert_eq!(loaded.fitness(&episode(Outcome::Collision)), 10.0);
+
+        let custom = FitnessMethod::custom(|episode: &Episode| episode.steps as f32);
+        assert_eq!(custom.fitness(&episode(Outcome::Collision)), 40.0);
+        let loaded: FitnessMethod =
+            serde_json::from_str(&serde_json::to_string(&custom).unwrap()).unwrap();
+        assert!(matches!(loaded, FitnessMethod::Custom(None)));
+    }
+
+    #[test]
+    fn closures_are_fitness_functions() {
+        let doubled = |episode: &Episode| episode.score as f32 * 2.0;
+        assert_eq!(doubled.fitness(&episode(Outcome::Collision)), 40.0);
+    }
+}
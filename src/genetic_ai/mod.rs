@@ -1,112 +1,216 @@
+pub mod bytecode;
+pub mod checkpoint;
+pub mod crossover;
+pub mod dot;
+pub mod evaluation;
+pub mod fitness;
+pub mod mutation;
 pub mod population;
+pub mod selection;
+pub mod sexpr;
+pub mod simplify;
+pub mod typed;
+pub mod typed_population;
 use std::fmt::Display;
 
 use rand::{distributions::Standard, prelude::*};
+use serde::{Deserialize, Serialize};
 
+use self::{
+    bytecode::Program,
+    evaluation::{Episode, EvaluationStats},
+};
 use crate::gym::{
+    environment::Policy,
     game::{Board, Game},
     snake::{Direction, Snake},
 };
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Agent {
-    pub fitness: u32,
+    pub fitness: f32,
+    //per episode fitness cases, used by lexicase selection
+    pub cases: Vec<f32>,
+    pub episodes: Vec<Episode>,
     pub gene: Tree,
+    #[serde(skip)]
     pub final_board: Option<Board>,
+    #[serde(skip)]
     pub final_snake: Option<Snake>,
 }
 
 impl Agent {
-    pub fn new(depth_limit: u32, method: Method) -> Agent {
+    pub fn new<R: Rng + ?Sized>(depth_limit: u32, method: Method, rng: &mut R) -> Agent {
+        Agent {
+            fitness: 0.0,
+            cases: Vec::new(),
+            episodes: Vec::new(),
+            gene: Tree::new(depth_limit, method, rng),
+            final_board: None,
+            final_snake: None,
+        }
+    }
+    pub fn from_gene(gene: Tree) -> Agent {
         Agent {
-            fitness: 0,
-            gene: Tree::new(depth_limit, method),
+            fitness: 0.0,
+            cases: Vec::new(),
+            episodes: Vec::new(),
+            gene,
             final_board: None,
             final_snake: None,
         }
     }
-    pub fn evaluate(&mut self, state: &Game) -> f32 {
+    pub fn stats(&self) -> EvaluationStats {
+        EvaluationStats::new(&self.episodes)
+    }
+    pub fn policy<'a, R: Rng + ?Sized>(&'a self, rng: &'a mut R) -> TreePolicy<'a, R> {
+        let program = self.gene.compile();
+        TreePolicy {
+            agent: self,
+            buffer: program.buffer(),
+            program,
+            rng,
+        }
+    }
+    pub fn evaluate<R: Rng + ?Sized>(&self, state: &Game, rng: &mut R) -> f32 {
         //decide which direction to go
         // let current = &self.gene.root;
         // self.fitness = self.eval_recurse(state, current);
         let current = &self.gene.root;
-        let test = self.eval_recurse(state, current);
-        return test;
+        self.eval_recurse(state, current, rng)
     }
-    pub fn eval_recurse(&self, state: &Game, node: &Node) -> f32 {
+    pub fn eval_recurse<R: Rng + ?Sized>(&self, state: &Game, node: &Node, rng: &mut R) -> f32 {
         match &node.node_type {
             NodeType::Leaf(leaf) => {
-                let temp = leaf.get_value(state);
+                let temp = leaf.get_value(state, rng);
                 if temp.is_nan() {
                     println!("found nan in leaf");
                 }
-                return leaf.get_value(state);
+                temp
             }
             NodeType::Internal(branch) => {
-                let left = self.eval_recurse(state, &node.left.as_ref().unwrap());
-                let right = self.eval_recurse(state, &node.right.as_ref().unwrap());
-                if left.is_nan() || right.is_nan() {
+                //every child is evaluated, even the branch a conditional discards, so
+                //random leaves always draw the same numbers
+                let args: Vec<f32> = node
+                    .children
+                    .iter()
+                    .map(|child| self.eval_recurse(state, child, rng))
+                    .collect();
+                if args.iter().any(|arg| arg.is_nan()) {
                     println!("found nan in internal");
                 }
-                return branch.eval(left, right);
+                branch.eval(&args, state)
             }
         }
     }
 }
 
+//runs the agent's tree compiled to bytecode, which scores the same as `Agent::evaluate`
+pub struct TreePolicy<'a, R: Rng + ?Sized> {
+    pub agent: &'a Agent,
+    pub program: Program,
+    buffer: Vec<f32>,
+    pub rng: &'a mut R,
+}
+
+impl<R: Rng + ?Sized> Policy<Game> for TreePolicy<'_, R> {
+    fn act(&mut self, game: &Game) -> Direction {
+        //score every state reachable in one move and take the best
+        game.get_possible_states()
+            .iter()
+            .map(|(dir, state)| {
+                let score = self.program.eval_with(state, self.rng, &mut self.buffer);
+                (*dir, score)
+            })
+            .max_by(|(_, a), (_, b)| {
+                assert!(!a.is_nan(), "found nan");
+                assert!(!b.is_nan(), "found nan");
+                assert!(*a != f32::INFINITY, "found inf");
+                assert!(*b != f32::INFINITY, "found inf");
+                assert!(*a != -f32::INFINITY, "found -inf");
+                assert!(*b != -f32::INFINITY, "found -inf");
+
+                a.partial_cmp(b).unwrap()
+            })
+            .unwrap()
+            .0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Method {
     Grow,
     Full,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Tree {
     pub root: Node,
 }
 impl Tree {
-    fn new(depth_limit: u32, method: Method) -> Tree {
+    pub fn size(&self) -> usize {
+        self.root.size()
+    }
+    pub fn depth(&self) -> u32 {
+        self.root.depth()
+    }
+    pub fn nodes(&self) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        self.root.collect(&mut nodes);
+        nodes
+    }
+    //nodes are addressed by their position in a preorder walk
+    pub fn subtree(&self, index: usize) -> &Node {
+        self.nodes()[index]
+    }
+    pub fn subtree_mut(&mut self, index: usize) -> &mut Node {
+        let mut remaining = index;
+        self.root
+            .find_mut(&mut remaining)
+            .expect("subtree index out of range")
+    }
+
+    fn new<R: Rng + ?Sized>(depth_limit: u32, method: Method, rng: &mut R) -> Tree {
         let starting_node = match method {
-            Method::Grow => Tree::grow(depth_limit, 0, 0.5),
-            Method::Full => Tree::full(depth_limit, 0),
+            Method::Grow => Tree::grow(depth_limit, 0, 0.5, rng),
+            Method::Full => Tree::full(depth_limit, 0, rng),
         };
         Tree {
             root: starting_node,
         }
     }
 
-    fn grow(depth_limit: u32, curr_level: u32, threshhold: f32) -> Node {
+    fn grow<R: Rng + ?Sized>(
+        depth_limit: u32,
+        curr_level: u32,
+        threshhold: f32,
+        rng: &mut R,
+    ) -> Node {
         if curr_level == depth_limit {
-            return Node::new_leaf();
+            return Node::new_leaf(rng);
         }
-        let mut rng = rand::thread_rng();
         let rand_num: f32 = rng.gen();
         if rand_num < threshhold {
-            let mut new_node = Node::new_internal();
-            new_node.left = Some(Box::new(Tree::grow(
-                depth_limit,
-                curr_level + 1,
-                threshhold,
-            )));
-            new_node.right = Some(Box::new(Tree::grow(
-                depth_limit,
-                curr_level + 1,
-                threshhold,
-            )));
-            return new_node;
+            let mut new_node = Node::new_internal(rng);
+            for _ in 0..new_node.arity() {
+                let child = Tree::grow(depth_limit, curr_level + 1, threshhold, rng);
+                new_node.children.push(child);
+            }
+            new_node
         } else {
-            return Node::new_leaf();
+            Node::new_leaf(rng)
         }
     }
-    fn full(depth_limit: u32, curr_level: u32) -> Node {
-        if curr_level == 0 {
-            let mut new_node = Node::new_internal();
-            new_node.left = Some(Box::new(Tree::full(depth_limit, curr_level + 1)));
-            new_node.right = Some(Box::new(Tree::full(depth_limit, curr_level + 1)));
-            return new_node;
-        } else if curr_level == depth_limit {
-            return Node::new_leaf();
+    fn full<R: Rng + ?Sized>(depth_limit: u32, curr_level: u32, rng: &mut R) -> Node {
+        if curr_level >= depth_limit && curr_level != 0 {
+            Node::new_leaf(rng)
         } else {
-            let mut new_node = Node::new_internal();
-            new_node.left = Some(Box::new(Tree::full(depth_limit, curr_level + 1)));
-            new_node.right = Some(Box::new(Tree::full(depth_limit, curr_level + 1)));
-            return new_node;
+            let mut new_node = Node::new_internal(rng);
+            for _ in 0..new_node.arity() {
+                new_node
+                    .children
+                    .push(Tree::full(depth_limit, curr_level + 1, rng));
+            }
+            new_node
         }
     }
     fn print(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -121,12 +225,9 @@ impl Tree {
         for _ in 0..depth {
             write!(f, "|")?;
         }
-        write!(f, "{}\n", node)?;
-        if let Some(left) = &node.left {
-            self.print_recurse(&left, depth + 1, f)?;
-        }
-        if let Some(right) = &node.right {
-            self.print_recurse(&right, depth + 1, f)?;
+        writeln!(f, "{}", node)?;
+        for child in &node.children {
+            self.print_recurse(child, depth + 1, f)?;
         }
         Ok(())
     }
@@ -139,46 +240,94 @@ impl Display for Tree {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Node {
     pub node_type: NodeType,
-    pub left: Option<Box<Node>>,
-    pub right: Option<Box<Node>>,
+    //as many as the primitive's arity, empty for leaves
+    pub children: Vec<Node>,
 }
 impl Node {
+    pub fn is_leaf(&self) -> bool {
+        matches!(self.node_type, NodeType::Leaf(_))
+    }
+    pub fn arity(&self) -> usize {
+        match &self.node_type {
+            NodeType::Internal(internal) => internal.arity(),
+            NodeType::Leaf(_) => 0,
+        }
+    }
+    pub fn children(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter()
+    }
+    pub fn size(&self) -> usize {
+        1 + self.children().map(Node::size).sum::<usize>()
+    }
+    pub fn depth(&self) -> u32 {
+        self.children()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+    fn collect<'a>(&'a self, nodes: &mut Vec<&'a Node>) {
+        nodes.push(self);
+        for child in self.children() {
+            child.collect(nodes);
+        }
+    }
+    //every constant leaf's value in preorder
+    fn constants_mut<'a>(&'a mut self, constants: &mut Vec<&'a mut f32>) {
+        if let NodeType::Leaf(LeafNode::Constant(value)) = &mut self.node_type {
+            constants.push(value);
+        }
+        for child in &mut self.children {
+            child.constants_mut(constants);
+        }
+    }
+    fn find_mut(&mut self, remaining: &mut usize) -> Option<&mut Node> {
+        if *remaining == 0 {
+            return Some(self);
+        }
+        *remaining -= 1;
+        for child in &mut self.children {
+            if let Some(found) = child.find_mut(remaining) {
+                return Some(found);
+            }
+        }
+        None
+    }
     pub fn new(node_type: NodeType) -> Node {
         Node {
             node_type,
-            left: None,
-            right: None,
+            children: Vec::new(),
         }
     }
-    fn new_leaf() -> Node {
-        let node: LeafNode = rand::random();
+    pub fn with_children(node_type: NodeType, children: Vec<Node>) -> Node {
         Node {
-            node_type: NodeType::Leaf(node),
-            left: None,
-            right: None,
+            node_type,
+            children,
         }
     }
-    fn new_internal() -> Node {
-        let node: InternalNode = rand::random();
-        Node {
-            node_type: NodeType::Internal(node),
-            left: None,
-            right: None,
-        }
+    fn new_leaf<R: Rng + ?Sized>(rng: &mut R) -> Node {
+        let node: LeafNode = rng.gen();
+        Node::new(NodeType::Leaf(node))
+    }
+    //children are left for the caller to fill in
+    fn new_internal<R: Rng + ?Sized>(rng: &mut R) -> Node {
+        let node: InternalNode = rng.gen();
+        Node::new(NodeType::Internal(node))
     }
 }
 impl Display for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.node_type {
+            NodeType::Leaf(LeafNode::Constant(value)) => write!(f, "{}", value),
             NodeType::Leaf(leaf) => write!(f, "{:?}", leaf),
             NodeType::Internal(internal) => write!(f, "{:?}", internal),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InternalNode {
     Add,
     Sub,
@@ -186,38 +335,100 @@ pub enum InternalNode {
     Div,
     Max,
     Min,
+    //(IfLessThan a b then else)
+    IfLessThan,
+    //(IfDanger then else), taking `then` when carrying on straight would be fatal
+    IfDanger,
+    Abs,
+    Neg,
+    //of the absolute value
+    Sqrt,
+    Sigmoid,
 }
 impl InternalNode {
-    fn eval(&self, left: f32, right: f32) -> f32 {
+    pub const ALL: [InternalNode; 12] = [
+        InternalNode::Add,
+        InternalNode::Sub,
+        InternalNode::Mul,
+        InternalNode::Div,
+        InternalNode::Max,
+        InternalNode::Min,
+        InternalNode::IfLessThan,
+        InternalNode::IfDanger,
+        InternalNode::Abs,
+        InternalNode::Neg,
+        InternalNode::Sqrt,
+        InternalNode::Sigmoid,
+    ];
+    pub fn arity(&self) -> usize {
         match self {
-            InternalNode::Add => left + right,
-            InternalNode::Sub => left - right,
-            InternalNode::Mul => left * right,
-            InternalNode::Div => {
-                if right == 0.0 {
-                    return left;
+            InternalNode::Abs | InternalNode::Neg | InternalNode::Sqrt | InternalNode::Sigmoid => 1,
+            InternalNode::IfLessThan => 4,
+            _ => 2,
+        }
+    }
+    fn eval(&self, args: &[f32], state: &Game) -> f32 {
+        match self {
+            InternalNode::IfDanger => {
+                if state.danger_ahead() {
+                    args[0]
+                } else {
+                    args[1]
                 }
-                left / right
             }
-            InternalNode::Max => left.max(right),
-            InternalNode::Min => left.min(right),
+            _ => self.fold(args).unwrap(),
         }
     }
+    //the value from the arguments alone, `None` for primitives that also look at the game
+    fn fold(&self, args: &[f32]) -> Option<f32> {
+        let value = match self {
+            InternalNode::Add => args[0] + args[1],
+            InternalNode::Sub => args[0] - args[1],
+            InternalNode::Mul => args[0] * args[1],
+            InternalNode::Div => {
+                if args[1] == 0.0 {
+                    return Some(args[0]);
+                }
+                args[0] / args[1]
+            }
+            InternalNode::Max => args[0].max(args[1]),
+            InternalNode::Min => args[0].min(args[1]),
+            InternalNode::IfLessThan => {
+                if args[0] < args[1] {
+                    args[2]
+                } else {
+                    args[3]
+                }
+            }
+            InternalNode::IfDanger => return None,
+            InternalNode::Abs => args[0].abs(),
+            InternalNode::Neg => -args[0],
+            InternalNode::Sqrt => args[0].abs().sqrt(),
+            InternalNode::Sigmoid => 1.0 / (1.0 + (-args[0]).exp()),
+        };
+        Some(value)
+    }
 }
 impl Distribution<InternalNode> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> InternalNode {
-        match rng.gen_range(0..6) {
+        match rng.gen_range(0..12) {
             0 => InternalNode::Add,
             1 => InternalNode::Sub,
             2 => InternalNode::Mul,
             3 => InternalNode::Div,
             4 => InternalNode::Max,
             5 => InternalNode::Min,
+            6 => InternalNode::IfLessThan,
+            7 => InternalNode::IfDanger,
+            8 => InternalNode::Abs,
+            9 => InternalNode::Neg,
+            10 => InternalNode::Sqrt,
+            11 => InternalNode::Sigmoid,
             _ => InternalNode::Add,
         }
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LeafNode {
     SnakeLength,
     SnakeDirection,
@@ -226,11 +437,47 @@ pub enum LeafNode {
     Random,
     Row,
     Column,
+    //steps to the nearest wall or body cell in an absolute direction
+    ObstacleUp,
+    ObstacleDown,
+    ObstacleLeft,
+    ObstacleRight,
+    //the same relative to the way the snake is heading
+    ObstacleAhead,
+    ObstacleToLeft,
+    ObstacleToRight,
+    //1 if carrying on straight kills the snake, 0 otherwise
+    NextCellDeadly,
+    //free cells the head can still reach
+    ReachableCells,
+    TailDistance,
+    //an ephemeral random constant, drawn once when the leaf is created
+    Constant(f32),
 }
 impl LeafNode {
-    fn get_value(&self, game: &Game) -> f32 {
+    //every terminal that carries no value
+    pub const ALL: [LeafNode; 17] = [
+        LeafNode::SnakeLength,
+        LeafNode::SnakeDirection,
+        LeafNode::AppleDistance,
+        LeafNode::AppleDirection,
+        LeafNode::Random,
+        LeafNode::Row,
+        LeafNode::Column,
+        LeafNode::ObstacleUp,
+        LeafNode::ObstacleDown,
+        LeafNode::ObstacleLeft,
+        LeafNode::ObstacleRight,
+        LeafNode::ObstacleAhead,
+        LeafNode::ObstacleToLeft,
+        LeafNode::ObstacleToRight,
+        LeafNode::NextCellDeadly,
+        LeafNode::ReachableCells,
+        LeafNode::TailDistance,
+    ];
+    fn get_value<R: Rng + ?Sized>(&self, game: &Game, rng: &mut R) -> f32 {
         match self {
-            LeafNode::SnakeLength => game.snake.body.length as f32,
+            LeafNode::SnakeLength => game.snake.len() as f32,
             LeafNode::SnakeDirection => match game.snake.direction {
                 Direction::Up => 0.0,
                 Direction::Down => 1.0,
@@ -238,23 +485,22 @@ impl LeafNode {
                 Direction::Right => 3.0,
             },
             LeafNode::AppleDistance => {
-                let apple_loc = game.apple.location;
-                let snake_head = match &game.snake.body.head {
-                    Some(head) => head,
+                let apple_loc = match game.nearest_apple() {
+                    Some(apple) => apple.location,
                     None => return 0.0,
                 };
+                let snake_head = game.snake.head();
                 //manhattan distance
-                ((apple_loc.x - snake_head.value.x).abs()
-                    + (apple_loc.y - snake_head.value.y).abs()) as f32
+                ((apple_loc.x - snake_head.x).abs() + (apple_loc.y - snake_head.y).abs()) as f32
             }
             LeafNode::AppleDirection => {
-                let apple_loc = game.apple.location;
-                let snake_head = match &game.snake.body.head {
-                    Some(head) => head,
+                let apple_loc = match game.nearest_apple() {
+                    Some(apple) => apple.location,
                     None => return 0.0,
                 };
-                let x_diff = apple_loc.x as f32 - snake_head.value.x as f32;
-                let y_diff = apple_loc.y as f32 - snake_head.value.y as f32;
+                let snake_head = game.snake.head();
+                let x_diff = apple_loc.x as f32 - snake_head.x as f32;
+                let y_diff = apple_loc.y as f32 - snake_head.y as f32;
                 let angle = y_diff.atan2(x_diff);
                 let snake_angle = match game.snake.direction {
                     Direction::Up => 0.0,
@@ -262,18 +508,35 @@ impl LeafNode {
                     Direction::Left => 2.0,
                     Direction::Right => 3.0,
                 };
-                let angle_diff = angle - snake_angle;
-                angle_diff
+                angle - snake_angle
+            }
+            LeafNode::Random => rng.gen(),
+            LeafNode::Row => game.snake.head().y as f32,
+            LeafNode::Column => game.snake.head().x as f32,
+            LeafNode::ObstacleUp => game.obstacle_distance(Direction::Up) as f32,
+            LeafNode::ObstacleDown => game.obstacle_distance(Direction::Down) as f32,
+            LeafNode::ObstacleLeft => game.obstacle_distance(Direction::Left) as f32,
+            LeafNode::ObstacleRight => game.obstacle_distance(Direction::Right) as f32,
+            LeafNode::ObstacleAhead => game.obstacle_distance(game.snake.direction) as f32,
+            LeafNode::ObstacleToLeft => {
+                game.obstacle_distance(game.snake.direction.turn_left()) as f32
+            }
+            LeafNode::ObstacleToRight => {
+                game.obstacle_distance(game.snake.direction.turn_right()) as f32
+            }
+            LeafNode::NextCellDeadly => game.danger_ahead() as u8 as f32,
+            LeafNode::ReachableCells => game.reachable_cells() as f32,
+            LeafNode::TailDistance => {
+                let (head, tail) = (game.snake.head(), game.snake.tail());
+                ((head.x - tail.x).abs() + (head.y - tail.y).abs()) as f32
             }
-            LeafNode::Random => rand::random(),
-            LeafNode::Row => game.snake.body.head.as_ref().unwrap().value.y as f32,
-            LeafNode::Column => game.snake.body.head.as_ref().unwrap().value.x as f32,
+            LeafNode::Constant(value) => *value,
         }
     }
 }
 impl Distribution<LeafNode> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> LeafNode {
-        match rng.gen_range(0..7) {
+        match rng.gen_range(0..18) {
             0 => LeafNode::SnakeLength,
             1 => LeafNode::SnakeDirection,
             2 => LeafNode::AppleDistance,
@@ -281,10 +544,22 @@ impl Distribution<LeafNode> for Standard {
             4 => LeafNode::Random,
             5 => LeafNode::Row,
             6 => LeafNode::Column,
+            7 => LeafNode::ObstacleUp,
+            8 => LeafNode::ObstacleDown,
+            9 => LeafNode::ObstacleLeft,
+            10 => LeafNode::ObstacleRight,
+            11 => LeafNode::ObstacleAhead,
+            12 => LeafNode::ObstacleToLeft,
+            13 => LeafNode::ObstacleToRight,
+            14 => LeafNode::NextCellDeadly,
+            15 => LeafNode::ReachableCells,
+            16 => LeafNode::TailDistance,
+            17 => LeafNode::Constant(rng.gen_range(-1.0..1.0)),
             _ => LeafNode::SnakeLength,
         }
     }
 }
+#[derive(Debug, Clone, PartialEq)]
 pub enum NodeType {
     Internal(InternalNode),
     Leaf(LeafNode),
@@ -0,0 +1,273 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{InternalNode, Node, NodeType, Tree};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutationConfig {
+    //chance of each operator being picked, whatever is left over means no mutation
+    pub subtree: f32,
+    pub point: f32,
+    pub hoist: f32,
+    pub shrink: f32,
+    pub constant: f32,
+    //standard deviation of the noise added to constants
+    pub constant_sigma: f32,
+    pub subtree_depth: u32,
+    pub max_depth: u32,
+}
+
+impl Default for MutationConfig {
+    fn default() -> Self {
+        MutationConfig {
+            subtree: 0.1,
+            point: 0.1,
+            hoist: 0.02,
+            shrink: 0.02,
+            constant: 0.1,
+            constant_sigma: 0.1,
+            subtree_depth: 3,
+            max_depth: 17,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    Subtree,
+    Point,
+    Hoist,
+    Shrink,
+    Constant,
+}
+
+impl MutationConfig {
+    pub fn pick<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Mutation> {
+        let mut roll: f32 = rng.gen();
+        for (mutation, chance) in [
+            (Mutation::Subtree, self.subtree),
+            (Mutation::Point, self.point),
+            (Mutation::Hoist, self.hoist),
+            (Mutation::Shrink, self.shrink),
+            (Mutation::Constant, self.constant),
+        ] {
+            if roll < chance {
+                return Some(mutation);
+            }
+            roll -= chance;
+        }
+        None
+    }
+}
+
+pub fn mutate<R: Rng + ?Sized>(tree: &mut Tree, config: &MutationConfig, rng: &mut R) {
+    match config.pick(rng) {
+        Some(Mutation::Subtree) => subtree_mutation(tree, config, rng),
+        Some(Mutation::Point) => point_mutation(tree, rng),
+        Some(Mutation::Hoist) => hoist_mutation(tree, rng),
+        Some(Mutation::Shrink) => shrink_mutation(tree, rng),
+        Some(Mutation::Constant) => constant_mutation(tree, config.constant_sigma, rng),
+        None => {}
+    }
+}
+
+//replace a random subtree with a freshly grown one
+pub fn subtree_mutation<R: Rng + ?Sized>(tree: &mut Tree, config: &MutationConfig, rng: &mut R) {
+    let point = rng.gen_range(0..tree.size());
+    let grown = Tree::grow(config.subtree_depth, 0, 0.5, rng);
+    let replaced = std::mem::replace(tree.subtree_mut(point), grown);
+    if tree.depth() > config.max_depth {
+        *tree.subtree_mut(point) = replaced;
+    }
+}
+
+//swap a single node for another primitive of the same arity, keeping its children
+pub fn point_mutation<R: Rng + ?Sized>(tree: &mut Tree, rng: &mut R) {
+    let point = rng.gen_range(0..tree.size());
+    let node = tree.subtree_mut(point);
+    match node.node_type {
+        NodeType::Internal(original) => {
+            let candidates: Vec<InternalNode> = InternalNode::ALL
+                .into_iter()
+                .filter(|op| *op != original && op.arity() == original.arity())
+                .collect();
+            //a primitive with no others of its arity is left alone
+            if !candidates.is_empty() {
+                node.node_type = NodeType::Internal(candidates[rng.gen_range(0..candidates.len())]);
+            }
+        }
+        NodeType::Leaf(_) => {
+            let original = node.node_type.clone();
+            while node.node_type == original {
+                node.node_type = Node::new_leaf(rng).node_type;
+            }
+        }
+    }
+}
+
+//promote a random subtree to be the whole tree
+pub fn hoist_mutation<R: Rng + ?Sized>(tree: &mut Tree, rng: &mut R) {
+    let size = tree.size();
+    if size == 1 {
+        return;
+    }
+    let point = rng.gen_range(1..size);
+    tree.root = tree.subtree(point).clone();
+}
+
+//replace a random internal node with one of its own children
+pub fn shrink_mutation<R: Rng + ?Sized>(tree: &mut Tree, rng: &mut R) {
+    let internals: Vec<usize> = tree
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| !node.is_leaf())
+        .map(|(index, _)| index)
+        .collect();
+    if internals.is_empty() {
+        return;
+    }
+    let point = internals[rng.gen_range(0..internals.len())];
+    let node = tree.subtree_mut(point);
+    let child = rng.gen_range(0..node.children.len());
+    *node = node.children.swap_remove(child);
+}
+
+//nudge every constant in the tree by gaussian noise
+pub fn constant_mutation<R: Rng + ?Sized>(tree: &mut Tree, sigma: f32, rng: &mut R) {
+    let mut constants = Vec::new();
+    tree.root.constants_mut(&mut constants);
+    perturb(constants, sigma, rng);
+}
+
+//adds gaussian noise to each value in turn, for any kind of tree's constants
+pub fn perturb<R: Rng + ?Sized>(constants: Vec<&mut f32>, sigma: f32, rng: &mut R) {
+    for value in constants {
+        *value += sigma * gaussian(rng);
+    }
+}
+
+//standard normal sample by the box-muller transform
+pub fn gaussian<R: Rng + ?Sized>(rng: &mut R) -> f32 {
+    let u: f32 = 1.0 - rng.gen::<f32>();
+    let v: f32 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::genetic_ai::LeafNode;
+
+    fn tree() -> Tree {
+        Tree::from_sexpr("(Add (Mul Row Column) SnakeLength)").unwrap()
+    }
+
+    #[test]
+    fn point_mutation_keeps_shape() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..50 {
+            let original = tree();
+            let mut mutated = tree();
+            point_mutation(&mut mutated, &mut rng);
+            assert_eq!(mutated.size(), original.size());
+            let changed = original
+                .nodes()
+                .iter()
+                .zip(mutated.nodes())
+                .filter(|(a, b)| a.node_type != b.node_type)
+                .count();
+            assert_eq!(changed, 1);
+        }
+    }
+
+    #[test]
+    fn point_mutation_keeps_arity() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..50 {
+            let mut mutated = Tree::from_sexpr("(IfLessThan (Abs Row) Column Row Row)").unwrap();
+            point_mutation(&mut mutated, &mut rng);
+            let arities: Vec<usize> = mutated.nodes().iter().map(|node| node.arity()).collect();
+            assert_eq!(arities, vec![4, 1, 0, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn hoist_and_shrink_make_trees_smaller() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..50 {
+            let mut hoisted = tree();
+            hoist_mutation(&mut hoisted, &mut rng);
+            assert!(hoisted.size() < 5);
+
+            let mut shrunk = tree();
+            shrink_mutation(&mut shrunk, &mut rng);
+            assert!(shrunk.size() < 5);
+            assert!(shrunk.depth() <= 2);
+        }
+    }
+
+    #[test]
+    fn subtree_mutation_respects_max_depth() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let config = MutationConfig {
+            subtree_depth: 4,
+            max_depth: 3,
+            ..MutationConfig::default()
+        };
+        for _ in 0..50 {
+            let mut mutated = tree();
+            subtree_mutation(&mut mutated, &config, &mut rng);
+            assert!(mutated.depth() <= 3);
+        }
+    }
+
+    #[test]
+    fn constant_mutation_nudges_only_constants() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let original = Tree::from_sexpr("(Add (Mul Row 2.5) -1)").unwrap();
+        let mut drift = 0.0;
+        for _ in 0..200 {
+            let mut mutated = original.clone();
+            constant_mutation(&mut mutated, 0.1, &mut rng);
+            assert_eq!(mutated.subtree(2).node_type, original.subtree(2).node_type);
+            let (NodeType::Leaf(LeafNode::Constant(a)), NodeType::Leaf(LeafNode::Constant(b))) =
+                (&mutated.subtree(3).node_type, &mutated.subtree(4).node_type)
+            else {
+                panic!("constants were replaced");
+            };
+            assert!(*a != 2.5 && (a - 2.5).abs() < 1.0);
+            assert!(*b != -1.0 && (b + 1.0).abs() < 1.0);
+            drift += a - 2.5;
+        }
+        //centred on the original value
+        assert!((drift / 200.0).abs() < 0.03);
+    }
+
+    #[test]
+    fn pick_follows_configured_chances() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let only_hoist = MutationConfig {
+            subtree: 0.0,
+            point: 0.0,
+            hoist: 1.0,
+            shrink: 0.0,
+            ..MutationConfig::default()
+        };
+        let never = MutationConfig {
+            subtree: 0.0,
+            point: 0.0,
+            hoist: 0.0,
+            shrink: 0.0,
+            constant: 0.0,
+            ..MutationConfig::default()
+        };
+        for _ in 0..50 {
+            assert_eq!(only_hoist.pick(&mut rng), Some(Mutation::Hoist));
+            assert_eq!(never.pick(&mut rng), None);
+        }
+    }
+}
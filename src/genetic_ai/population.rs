@@ -1,83 +1,501 @@
-use std::f32::INFINITY;
+use std::{fmt::Display, sync::Arc};
 
-use crate::gym::{game::Game, snake::Direction};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-use super::{Agent, Method};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
 
+use crate::gym::{
+    game::{Game, GameConfig},
+    render::SharedRenderer,
+};
+
+use super::{
+    crossover::{crossover, CrossoverConfig},
+    evaluation::{play_episode, Episode},
+    fitness::{FitnessFunction, FitnessMethod},
+    mutation::{mutate, MutationConfig},
+    Agent, Method,
+};
+
+pub use super::selection::{Selection, SelectionMethod, Tournament};
+
+//the renderer is not serialized and a loaded config has none
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EvaluationConfig {
+    pub episodes: usize,
+    pub starvation_limit: u32,
+    pub fitness: FitnessMethod,
+    //worker threads, defaults to one per core as does zero
+    pub threads: Option<usize>,
+    //evaluation is silent without one. with one it runs on a single thread, one
+    //individual after another, so frames from different games never interleave
+    #[serde(skip)]
+    pub renderer: Option<SharedRenderer>,
+}
+
+impl Default for EvaluationConfig {
+    fn default() -> Self {
+        EvaluationConfig {
+            episodes: 5,
+            starvation_limit: 10000,
+            fitness: FitnessMethod::default(),
+            threads: None,
+            renderer: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for EvaluationConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvaluationConfig")
+            .field("episodes", &self.episodes)
+            .field("starvation_limit", &self.starvation_limit)
+            .field("fitness", &self.fitness)
+            .field("threads", &self.threads)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvolutionConfig {
+    pub evaluation: EvaluationConfig,
+    pub elites: usize,
+    pub selection: SelectionMethod,
+    pub crossover_rate: f32,
+    pub crossover: CrossoverConfig,
+    pub mutation: MutationConfig,
+}
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        EvolutionConfig {
+            evaluation: EvaluationConfig::default(),
+            elites: 1,
+            selection: SelectionMethod::Tournament(Tournament { size: 3 }),
+            crossover_rate: 0.9,
+            crossover: CrossoverConfig::default(),
+            mutation: MutationConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationStats {
+    pub generation: u32,
+    pub best: f32,
+    pub mean: f32,
+    pub median: f32,
+}
+
+impl GenerationStats {
+    pub fn new(generation: u32, mut fitness: Vec<f32>) -> GenerationStats {
+        fitness.sort_by(f32::total_cmp);
+        let count = fitness.len();
+        let median = if count == 0 {
+            0.0
+        } else if count.is_multiple_of(2) {
+            (fitness[count / 2 - 1] + fitness[count / 2]) / 2.0
+        } else {
+            fitness[count / 2]
+        };
+        GenerationStats {
+            generation,
+            best: fitness.last().copied().unwrap_or(0.0),
+            mean: fitness.iter().sum::<f32>() / count.max(1) as f32,
+            median,
+        }
+    }
+}
+
+impl Display for GenerationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "generation {}: best {:.2} mean {:.2} median {:.2}",
+            self.generation, self.best, self.mean, self.median
+        )
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Population {
     pub individuals: Vec<Agent>,
+    pub rng: ChaCha8Rng,
+    //how many times the population has been bred
+    pub generation: u32,
+    //built on the first evaluation that asks for a thread count and kept until a
+    //different count is asked for
+    #[serde(skip)]
+    pool: Option<Arc<ThreadPool>>,
 }
 impl Population {
-    pub fn new(size: u32, depth_limit: u32) -> Population {
+    pub fn new(size: u32, depth_limit: u32, seed: u64) -> Population {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
         let mut individuals = Vec::new();
 
         for _ in 0..size / 2 {
-            individuals.push(Agent::new(depth_limit, Method::Grow));
+            individuals.push(Agent::new(depth_limit, Method::Grow, &mut rng));
         }
 
         for _ in size / 2..size {
-            individuals.push(Agent::new(depth_limit, Method::Full));
+            individuals.push(Agent::new(depth_limit, Method::Full, &mut rng));
+        }
+        Population {
+            individuals,
+            rng,
+            generation: 0,
+            pool: None,
         }
-        Population { individuals }
     }
-    pub fn evaluate(&mut self, game: &mut Game) {
-        println!("population size: {}", self.individuals.len());
-        for individual in &mut self.individuals {
-            let mut count = 0;
-
-            while !game.lost {
-                if count > 10000 {
-                    println!("found infinite loop");
-                    break;
-                }
-
-                let starting_score = game.score;
-                let moves = game.get_possible_states();
-
-                let best_move: Direction = moves
-                    .iter()
-                    .map(|(dir, game)| (dir, individual.evaluate(game)))
-                    .max_by(|(_, a), (_, b)| {
-                        assert!(!a.is_nan(), "found nan");
-                        assert!(!b.is_nan(), "found nan");
-                        assert!(*a != INFINITY, "found inf");
-                        assert!(*b != INFINITY, "found inf");
-                        assert!(*a != -INFINITY, "found -inf");
-                        assert!(*b != -INFINITY, "found -inf");
-
-                        return a.partial_cmp(b).unwrap();
-                    })
-                    .unwrap()
-                    .0
-                    .to_owned();
-
-                game.update_direction(best_move);
-                game.update();
-                game.display();
-
-                if game.score == starting_score {
-                    count += 1;
-                } else {
-                    count = 0;
-                }
+    pub fn from_individuals(individuals: Vec<Agent>, seed: u64) -> Population {
+        Population {
+            individuals,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            generation: 0,
+            pool: None,
+        }
+    }
+    //play every individual on the same freshly drawn episode seeds, spread across
+    //worker threads that each own a game built from `game_config`
+    pub fn evaluate(&mut self, game_config: &GameConfig, config: &EvaluationConfig) {
+        let seeds: Vec<u64> = (0..config.episodes).map(|_| self.rng.gen()).collect();
+        evaluate_each(
+            &mut self.individuals,
+            &mut self.pool,
+            game_config,
+            config,
+            |individual, game| evaluate_individual(individual, game, &seeds, config),
+        );
+    }
+    //evaluate and breed for the given number of generations, leaving the last one evaluated
+    pub fn evolve(
+        &mut self,
+        game_config: &GameConfig,
+        generations: u32,
+        config: &EvolutionConfig,
+    ) -> Vec<GenerationStats> {
+        let mut history = Vec::new();
+        for remaining in (0..generations).rev() {
+            self.evaluate(game_config, &config.evaluation);
+            history.push(self.stats(self.generation));
+            if remaining > 0 {
+                self.next_generation(config);
             }
+        }
+        history
+    }
+    pub fn stats(&self, generation: u32) -> GenerationStats {
+        GenerationStats::new(
+            generation,
+            self.individuals.iter().map(|a| a.fitness).collect(),
+        )
+    }
+    //replace the population with elites plus offspring of selected parents
+    pub fn next_generation(&mut self, config: &EvolutionConfig) {
+        let size = self.individuals.len();
+        let mut ranked: Vec<usize> = (0..size).collect();
+        ranked.sort_by(|a, b| {
+            self.individuals[*b]
+                .fitness
+                .total_cmp(&self.individuals[*a].fitness)
+        });
 
-            individual.final_board = Some(game.board.clone());
-            individual.fitness = game.score;
-            individual.final_snake = Some(game.snake.clone());
-            game.reset();
+        let mut next: Vec<Agent> = ranked
+            .iter()
+            .take(config.elites.min(size))
+            .map(|index| self.individuals[*index].clone())
+            .collect();
+        let selector = config.selection.prepare(&self.individuals);
+        while next.len() < size {
+            let first = selector.select(&mut self.rng);
+            let second = selector.select(&mut self.rng);
+            let first = &self.individuals[first].gene;
+            let second = &self.individuals[second].gene;
+            let (mut first, mut second) = if self.rng.gen::<f32>() < config.crossover_rate {
+                crossover(first, second, &config.crossover, &mut self.rng)
+            } else {
+                (first.clone(), second.clone())
+            };
+            mutate(&mut first, &config.mutation, &mut self.rng);
+            mutate(&mut second, &config.mutation, &mut self.rng);
+            next.push(Agent::from_gene(first));
+            if next.len() < size {
+                next.push(Agent::from_gene(second));
+            }
         }
+        self.individuals = next;
+        self.generation += 1;
     }
-    pub fn mutate(&mut self) {
-        todo!()
+    pub fn mutate(&mut self, config: &MutationConfig) {
+        for individual in &mut self.individuals {
+            mutate(&mut individual.gene, config, &mut self.rng);
+        }
     }
-    pub fn crossover(&mut self) {
-        todo!()
+    //pair individuals up at random and replace each pair with its two children
+    pub fn crossover(&mut self, config: &CrossoverConfig) {
+        let mut parents = std::mem::take(&mut self.individuals);
+        parents.shuffle(&mut self.rng);
+        for pair in parents.chunks(2) {
+            match pair {
+                [first, second] => {
+                    let (first, second) =
+                        crossover(&first.gene, &second.gene, config, &mut self.rng);
+                    self.individuals.push(Agent::from_gene(first));
+                    self.individuals.push(Agent::from_gene(second));
+                }
+                [single] => self.individuals.push(Agent::from_gene(single.gene.clone())),
+                _ => unreachable!(),
+            }
+        }
     }
     pub fn return_best(&self) -> &Agent {
         self.individuals
             .iter()
-            .max_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap())
+            .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
             .unwrap()
     }
 }
+
+//runs `evaluate` on every individual with a game built from `game_config`, spread
+//across the cached pool's threads unless a renderer is attached
+pub(crate) fn evaluate_each<T: Send>(
+    individuals: &mut [T],
+    pool: &mut Option<Arc<ThreadPool>>,
+    game_config: &GameConfig,
+    config: &EvaluationConfig,
+    evaluate: impl Fn(&mut T, &mut Game) + Sync,
+) {
+    if let Some(renderer) = &config.renderer {
+        let mut game = Game::with_config(game_config.clone());
+        game.attach(renderer.clone());
+        for individual in individuals {
+            evaluate(individual, &mut game);
+        }
+        return;
+    }
+    let evaluate = &evaluate;
+    let mut evaluate_all = move || {
+        individuals.par_iter_mut().for_each_init(
+            || Game::with_config(game_config.clone()),
+            |game, individual| evaluate(individual, game),
+        )
+    };
+    match config.threads.filter(|threads| *threads > 0) {
+        Some(threads) => {
+            let pool = match pool {
+                Some(pool) if pool.current_num_threads() == threads => pool,
+                _ => pool.insert(Arc::new(
+                    ThreadPoolBuilder::new()
+                        .num_threads(threads)
+                        .build()
+                        .expect("failed to build evaluation thread pool"),
+                )),
+            };
+            pool.install(evaluate_all)
+        }
+        None => evaluate_all(),
+    }
+}
+
+//decisions get their own stream so identical trees score identically
+pub(crate) fn decisions(seed: u64) -> ChaCha8Rng {
+    let mut decisions = ChaCha8Rng::seed_from_u64(seed);
+    decisions.set_stream(1);
+    decisions
+}
+
+//the fitness of every episode and their mean
+pub(crate) fn score(episodes: &[Episode], config: &EvaluationConfig) -> (Vec<f32>, f32) {
+    let cases: Vec<f32> = episodes.iter().map(|e| config.fitness.fitness(e)).collect();
+    let fitness = cases.iter().sum::<f32>() / cases.len().max(1) as f32;
+    (cases, fitness)
+}
+
+fn evaluate_individual(
+    individual: &mut Agent,
+    game: &mut Game,
+    seeds: &[u64],
+    config: &EvaluationConfig,
+) {
+    let mut episodes = Vec::new();
+    for seed in seeds {
+        let mut decisions = decisions(*seed);
+        let mut policy = individual.policy(&mut decisions);
+        episodes.push(play_episode(
+            game,
+            &mut policy,
+            *seed,
+            config.starvation_limit,
+        ));
+    }
+
+    (individual.cases, individual.fitness) = score(&episodes, config);
+    individual.episodes = episodes;
+    individual.final_board = Some(game.board.clone());
+    individual.final_snake = Some(game.snake.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::gym::render::{Frame, FrameCapture};
+
+    #[test]
+    fn same_seed_evolves_same_champion() {
+        let champion = |seed| {
+            let game = GameConfig::new().seed(seed).step_limit(200);
+            let mut population = Population::new(6, 3, seed);
+            let history = population.evolve(&game, 3, &EvolutionConfig::default());
+            let best = population.return_best();
+            (history, best.fitness, best.gene.to_string())
+        };
+        assert_eq!(champion(42), champion(42));
+        assert_ne!(champion(42), champion(43));
+    }
+
+    #[test]
+    fn thread_count_does_not_change_results() {
+        let fitness = |threads| {
+            let mut population = Population::new(12, 4, 3);
+            let config = EvaluationConfig {
+                episodes: 2,
+                threads: Some(threads),
+                ..EvaluationConfig::default()
+            };
+            population.evaluate(&GameConfig::new().step_limit(300), &config);
+            population
+                .individuals
+                .iter()
+                .map(|a| a.cases.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fitness(1), fitness(4));
+    }
+
+    #[test]
+    fn thread_pool_is_built_once() {
+        let game = GameConfig::new().size(6, 6).step_limit(50);
+        let config = EvaluationConfig {
+            episodes: 1,
+            threads: Some(2),
+            ..EvaluationConfig::default()
+        };
+        let mut population = Population::new(4, 3, 2);
+        population.evaluate(&game, &config);
+        let pool = population.pool.clone().unwrap();
+        population.evaluate(&game, &config);
+        assert!(Arc::ptr_eq(&pool, population.pool.as_ref().unwrap()));
+
+        population.evaluate(
+            &game,
+            &EvaluationConfig {
+                threads: Some(3),
+                ..config.clone()
+            },
+        );
+        assert_eq!(population.pool.as_ref().unwrap().current_num_threads(), 3);
+
+        population.pool = None;
+        population.evaluate(
+            &game,
+            &EvaluationConfig {
+                threads: Some(0),
+                ..config
+            },
+        );
+        assert!(population.pool.is_none());
+    }
+
+    #[test]
+    fn rendered_evaluation_plays_one_game_at_a_time() {
+        let capture = Arc::new(Mutex::new(FrameCapture::new()));
+        let mut population = Population::new(6, 3, 4);
+        let config = EvaluationConfig {
+            episodes: 2,
+            threads: Some(4),
+            renderer: Some(capture.clone()),
+            ..EvaluationConfig::default()
+        };
+        population.evaluate(&GameConfig::new().size(6, 6).step_limit(60), &config);
+
+        //every episode is a reset frame followed by its own steps up to the loss
+        let frames = &capture.lock().unwrap().frames;
+        let mut endings = Vec::new();
+        let mut previous: Option<&Frame> = None;
+        for frame in frames {
+            match previous {
+                Some(previous) if !previous.lost => assert!(frame.steps >= previous.steps),
+                _ => assert_eq!(frame.steps, 0),
+            }
+            if frame.lost {
+                endings.push((frame.score, frame.steps));
+            }
+            previous = Some(frame);
+        }
+        let expected: Vec<(u32, u32)> = population
+            .individuals
+            .iter()
+            .flat_map(|a| a.episodes.iter().map(|e| (e.score, e.steps)))
+            .collect();
+        assert_eq!(endings, expected);
+    }
+
+    #[test]
+    fn every_individual_plays_the_same_seeds() {
+        let game = GameConfig::new().size(6, 6).step_limit(100);
+        let mut population = Population::new(1, 3, 9);
+        population
+            .individuals
+            .push(population.individuals[0].clone());
+        let config = EvaluationConfig {
+            episodes: 4,
+            ..EvaluationConfig::default()
+        };
+        population.evaluate(&game, &config);
+
+        let (first, second) = (&population.individuals[0], &population.individuals[1]);
+        assert_eq!(first.episodes.len(), 4);
+        assert_eq!(first.episodes, second.episodes);
+        assert_eq!(first.fitness, first.stats().score.mean);
+        assert!(first.stats().steps.max <= 100.0);
+    }
+
+    #[test]
+    fn fitness_comes_from_the_configured_function() {
+        let game = GameConfig::new().size(6, 6).step_limit(50);
+        let mut population = Population::new(4, 3, 5);
+        let config = EvaluationConfig {
+            episodes: 3,
+            fitness: FitnessMethod::custom(|episode: &Episode| episode.steps as f32),
+            ..EvaluationConfig::default()
+        };
+        population.evaluate(&game, &config);
+        for individual in &population.individuals {
+            assert_eq!(individual.fitness, individual.stats().steps.mean);
+        }
+    }
+
+    #[test]
+    fn evolve_reports_every_generation() {
+        let game = GameConfig::new().size(6, 6).step_limit(200);
+        let mut population = Population::new(8, 3, 1);
+        let config = EvolutionConfig {
+            elites: 2,
+            ..EvolutionConfig::default()
+        };
+        let history = population.evolve(&game, 4, &config);
+
+        assert_eq!(history.len(), 4);
+        assert_eq!(population.individuals.len(), 8);
+        for (generation, stats) in history.iter().enumerate() {
+            assert_eq!(stats.generation, generation as u32);
+            assert!(stats.mean <= stats.best);
+            assert!(stats.median <= stats.best);
+        }
+        assert_eq!(population.return_best().fitness, history[3].best);
+    }
+}
@@ -0,0 +1,344 @@
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::Agent;
+
+//what selection needs to know about an individual, so any kind of gene can be bred
+pub trait Scored {
+    fn fitness(&self) -> f32;
+    //one fitness per episode, for lexicase
+    fn cases(&self) -> &[f32];
+}
+
+impl Scored for Agent {
+    fn fitness(&self) -> f32 {
+        self.fitness
+    }
+    fn cases(&self) -> &[f32] {
+        &self.cases
+    }
+}
+
+pub trait Selection<A: Scored = Agent> {
+    //returns the index of the chosen parent
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tournament {
+    pub size: usize,
+}
+
+impl<A: Scored> Selection<A> for Tournament {
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize {
+        (0..self.size.max(1))
+            .map(|_| rng.gen_range(0..individuals.len()))
+            .max_by(|a, b| {
+                individuals[*a]
+                    .fitness()
+                    .total_cmp(&individuals[*b].fitness())
+            })
+            .unwrap()
+    }
+}
+
+//fitness proportionate, shifted so the worst is never below zero and falling back
+//to uniform when nobody scored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Roulette;
+
+impl<A: Scored> Selection<A> for Roulette {
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize {
+        let floor = individuals.iter().map(|a| a.fitness()).fold(0.0, f32::min);
+        let total: f32 = individuals.iter().map(|a| a.fitness() - floor).sum();
+        if total <= 0.0 {
+            return rng.gen_range(0..individuals.len());
+        }
+        let mut spin = rng.gen_range(0.0..total);
+        for (index, individual) in individuals.iter().enumerate() {
+            let slice = individual.fitness() - floor;
+            if spin < slice {
+                return index;
+            }
+            spin -= slice;
+        }
+        individuals.len() - 1
+    }
+}
+
+//linear ranking, the worst gets weight 1 and the best gets weight n
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rank;
+
+impl<A: Scored> Selection<A> for Rank {
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize {
+        let ranked = ranked(individuals);
+        let count = ranked.len() as u64;
+        let mut spin = rng.gen_range(0..count * (count + 1) / 2);
+        for (position, index) in ranked.iter().enumerate() {
+            let weight = count - position as u64;
+            if spin < weight {
+                return *index;
+            }
+            spin -= weight;
+        }
+        ranked[0]
+    }
+}
+
+//uniform among the top fraction of the population
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Truncation {
+    pub fraction: f32,
+}
+
+impl<A: Scored> Selection<A> for Truncation {
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize {
+        let ranked = ranked(individuals);
+        let keep = ((ranked.len() as f32 * self.fraction).ceil() as usize).clamp(1, ranked.len());
+        ranked[rng.gen_range(0..keep)]
+    }
+}
+
+//epsilon lexicase over the per episode fitness cases, epsilon defaults to the
+//median absolute deviation of each case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lexicase {
+    pub epsilon: Option<f32>,
+}
+
+impl Lexicase {
+    //the tolerance for every case, which only changes when the population does
+    pub fn epsilons<A: Scored>(&self, individuals: &[A]) -> Vec<f32> {
+        let case_count = individuals
+            .iter()
+            .map(|a| a.cases().len())
+            .min()
+            .unwrap_or(0);
+        (0..case_count)
+            .map(|case| match self.epsilon {
+                Some(epsilon) => epsilon,
+                None => {
+                    median_absolute_deviation(individuals.iter().map(|a| a.cases()[case]).collect())
+                }
+            })
+            .collect()
+    }
+    pub fn select_with<A: Scored>(
+        &self,
+        individuals: &[A],
+        epsilons: &[f32],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        let mut cases: Vec<usize> = (0..epsilons.len()).collect();
+        cases.shuffle(rng);
+
+        let mut candidates: Vec<usize> = (0..individuals.len()).collect();
+        for case in cases {
+            if candidates.len() == 1 {
+                break;
+            }
+            let epsilon = epsilons[case];
+            let best = candidates
+                .iter()
+                .map(|index| individuals[*index].cases()[case])
+                .fold(f32::NEG_INFINITY, f32::max);
+            candidates.retain(|index| individuals[*index].cases()[case] >= best - epsilon);
+        }
+        candidates[rng.gen_range(0..candidates.len())]
+    }
+}
+
+impl<A: Scored> Selection<A> for Lexicase {
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize {
+        self.select_with(individuals, &self.epsilons(individuals), rng)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SelectionMethod {
+    Tournament(Tournament),
+    Roulette(Roulette),
+    Rank(Rank),
+    Truncation(Truncation),
+    Lexicase(Lexicase),
+}
+
+impl<A: Scored> Selection<A> for SelectionMethod {
+    fn select(&self, individuals: &[A], rng: &mut dyn RngCore) -> usize {
+        match self {
+            SelectionMethod::Tournament(method) => method.select(individuals, rng),
+            SelectionMethod::Roulette(method) => method.select(individuals, rng),
+            SelectionMethod::Rank(method) => method.select(individuals, rng),
+            SelectionMethod::Truncation(method) => method.select(individuals, rng),
+            SelectionMethod::Lexicase(method) => method.select(individuals, rng),
+        }
+    }
+}
+
+impl SelectionMethod {
+    //for picking many parents from the same individuals, so anything that depends
+    //only on them is worked out once
+    pub fn prepare<'a, A: Scored>(&'a self, individuals: &'a [A]) -> Selector<'a, A> {
+        let epsilons = match self {
+            SelectionMethod::Lexicase(method) => method.epsilons(individuals),
+            _ => Vec::new(),
+        };
+        Selector {
+            method: self,
+            individuals,
+            epsilons,
+        }
+    }
+}
+
+pub struct Selector<'a, A: Scored = Agent> {
+    method: &'a SelectionMethod,
+    individuals: &'a [A],
+    epsilons: Vec<f32>,
+}
+
+impl<A: Scored> Selector<'_, A> {
+    pub fn select(&self, rng: &mut dyn RngCore) -> usize {
+        match self.method {
+            SelectionMethod::Lexicase(method) => {
+                method.select_with(self.individuals, &self.epsilons, rng)
+            }
+            method => method.select(self.individuals, rng),
+        }
+    }
+}
+
+//indices from best to worst
+fn ranked<A: Scored>(individuals: &[A]) -> Vec<usize> {
+    let mut ranked: Vec<usize> = (0..individuals.len()).collect();
+    ranked.sort_by(|a, b| {
+        individuals[*b]
+            .fitness()
+            .total_cmp(&individuals[*a].fitness())
+    });
+    ranked
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+fn median_absolute_deviation(values: Vec<f32>) -> f32 {
+    let center = median(values.clone());
+    median(values.iter().map(|value| (value - center).abs()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::genetic_ai::{LeafNode, Node, NodeType, Tree};
+
+    fn agent(fitness: f32, cases: Vec<f32>) -> Agent {
+        let mut agent = Agent::from_gene(Tree {
+            root: Node::new(NodeType::Leaf(LeafNode::Random)),
+        });
+        agent.fitness = fitness;
+        agent.cases = cases;
+        agent
+    }
+
+    fn counts(method: &dyn Selection, individuals: &[Agent]) -> Vec<usize> {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut counts = vec![0; individuals.len()];
+        for _ in 0..2000 {
+            counts[method.select(individuals, &mut rng)] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn fitter_individuals_are_chosen_more_often() {
+        let individuals = vec![agent(0.0, vec![]), agent(5.0, vec![]), agent(20.0, vec![])];
+        for method in [
+            SelectionMethod::Tournament(Tournament { size: 3 }),
+            SelectionMethod::Roulette(Roulette),
+            SelectionMethod::Rank(Rank),
+        ] {
+            let counts = counts(&method, &individuals);
+            assert!(
+                counts[0] < counts[1] && counts[1] < counts[2],
+                "{:?}",
+                method
+            );
+        }
+    }
+
+    #[test]
+    fn roulette_never_picks_zero_fitness_when_others_scored() {
+        let individuals = vec![agent(0.0, vec![]), agent(5.0, vec![])];
+        assert_eq!(counts(&Roulette, &individuals)[0], 0);
+    }
+
+    #[test]
+    fn truncation_only_picks_the_top() {
+        let individuals = vec![
+            agent(3.0, vec![]),
+            agent(9.0, vec![]),
+            agent(1.0, vec![]),
+            agent(7.0, vec![]),
+        ];
+        let counts = counts(&Truncation { fraction: 0.5 }, &individuals);
+        assert_eq!(counts[0] + counts[2], 0);
+        assert!(counts[1] > 0 && counts[3] > 0);
+    }
+
+    #[test]
+    fn lexicase_keeps_specialists() {
+        //the generalist is never best on any single case
+        let individuals = vec![
+            agent(10.0, vec![10.0, 0.0]),
+            agent(10.0, vec![0.0, 10.0]),
+            agent(12.0, vec![6.0, 6.0]),
+        ];
+        let strict = counts(&Lexicase { epsilon: Some(0.0) }, &individuals);
+        assert_eq!(strict[2], 0);
+        assert!(strict[0] > 0 && strict[1] > 0);
+
+        //but a wide enough epsilon lets it through
+        let relaxed = counts(&Lexicase { epsilon: Some(5.0) }, &individuals);
+        assert!(relaxed[2] > 0);
+    }
+
+    #[test]
+    fn prepared_lexicase_picks_the_same_parents() {
+        let individuals = vec![
+            agent(3.0, vec![1.0, 2.0, 0.0]),
+            agent(4.0, vec![0.5, 2.5, 1.0]),
+            agent(5.0, vec![2.0, 0.0, 3.0]),
+            agent(2.0, vec![1.5, 1.5, 1.5]),
+        ];
+        let method = SelectionMethod::Lexicase(Lexicase { epsilon: None });
+        let SelectionMethod::Lexicase(lexicase) = &method else {
+            unreachable!()
+        };
+        assert_eq!(lexicase.epsilons(&individuals), vec![0.5, 0.5, 0.75]);
+
+        let selector = method.prepare(&individuals);
+        let mut first = ChaCha8Rng::seed_from_u64(6);
+        let mut second = ChaCha8Rng::seed_from_u64(6);
+        for _ in 0..200 {
+            assert_eq!(
+                method.select(&individuals, &mut first),
+                selector.select(&mut second)
+            );
+        }
+    }
+}
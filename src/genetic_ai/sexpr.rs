@@ -0,0 +1,285 @@
+use std::fmt::Display;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{InternalNode, LeafNode, Node, NodeType, Tree};
+
+//positions are byte offsets into the parsed string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedEnd,
+    UnexpectedToken {
+        token: String,
+        position: usize,
+    },
+    UnknownSymbol {
+        symbol: String,
+        position: usize,
+    },
+    //a leaf was written as `(Leaf ...)`
+    LeafInParens {
+        symbol: String,
+        position: usize,
+    },
+    Arity {
+        symbol: String,
+        expected: usize,
+        found: usize,
+        position: usize,
+    },
+    TrailingInput {
+        position: usize,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ParseError::UnexpectedToken { token, position } => {
+                write!(f, "unexpected `{}` at {}", token, position)
+            }
+            ParseError::UnknownSymbol { symbol, position } => {
+                write!(f, "unknown symbol `{}` at {}", symbol, position)
+            }
+            ParseError::LeafInParens { symbol, position } => write!(
+                f,
+                "`{}` at {} is a leaf and cannot be applied to arguments",
+                symbol, position
+            ),
+            ParseError::Arity {
+                symbol,
+                expected,
+                found,
+                position,
+            } => write!(
+                f,
+                "`{}` at {} takes {} arguments but was given {}",
+                symbol, position, expected, found
+            ),
+            ParseError::TrailingInput { position } => {
+                write!(f, "unexpected input after the tree at {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Tree {
+    //leaves are bare symbols or numbers and internal nodes are `(Op child...)`
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        write_node(&self.root, &mut out);
+        out
+    }
+    pub fn from_sexpr(input: &str) -> Result<Tree, ParseError> {
+        let mut tokens = Tokens::new(input);
+        let root = parse_node(&mut tokens)?;
+        match tokens.next() {
+            None => Ok(Tree { root }),
+            Some((_, position)) => Err(ParseError::TrailingInput { position }),
+        }
+    }
+}
+
+//trees are stored as their s-expression wherever they are serialized
+impl Serialize for Tree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_sexpr())
+    }
+}
+
+impl<'de> Deserialize<'de> for Tree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tree, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Tree::from_sexpr(&text).map_err(de::Error::custom)
+    }
+}
+
+fn write_node(node: &Node, out: &mut String) {
+    match &node.node_type {
+        NodeType::Leaf(_) => out.push_str(&node.to_string()),
+        NodeType::Internal(internal) => {
+            out.push_str(&format!("({:?}", internal));
+            for child in node.children() {
+                out.push(' ');
+                write_node(child, out);
+            }
+            out.push(')');
+        }
+    }
+}
+
+fn parse_node(tokens: &mut Tokens) -> Result<Node, ParseError> {
+    match tokens.next() {
+        None => Err(ParseError::UnexpectedEnd),
+        Some(("(", _)) => {
+            let (symbol, position) = tokens.next().ok_or(ParseError::UnexpectedEnd)?;
+            if symbol == "(" || symbol == ")" {
+                return Err(ParseError::UnexpectedToken {
+                    token: symbol.to_string(),
+                    position,
+                });
+            }
+            let internal = match lookup(symbol, position)? {
+                NodeType::Internal(internal) => internal,
+                NodeType::Leaf(_) => {
+                    return Err(ParseError::LeafInParens {
+                        symbol: symbol.to_string(),
+                        position,
+                    })
+                }
+            };
+            let mut children = Vec::new();
+            loop {
+                match tokens.peek() {
+                    None => return Err(ParseError::UnexpectedEnd),
+                    Some((")", _)) => {
+                        tokens.next();
+                        break;
+                    }
+                    Some(_) => children.push(parse_node(tokens)?),
+                }
+            }
+            if children.len() != internal.arity() {
+                return Err(ParseError::Arity {
+                    symbol: symbol.to_string(),
+                    expected: internal.arity(),
+                    found: children.len(),
+                    position,
+                });
+            }
+            Ok(Node::with_children(NodeType::Internal(internal), children))
+        }
+        Some((")", position)) => Err(ParseError::UnexpectedToken {
+            token: ")".to_string(),
+            position,
+        }),
+        Some((symbol, position)) => match lookup(symbol, position)? {
+            NodeType::Leaf(leaf) => Ok(Node::new(NodeType::Leaf(leaf))),
+            NodeType::Internal(internal) => Err(ParseError::Arity {
+                symbol: symbol.to_string(),
+                expected: internal.arity(),
+                found: 0,
+                position,
+            }),
+        },
+    }
+}
+
+fn lookup(symbol: &str, position: usize) -> Result<NodeType, ParseError> {
+    if let Some(internal) = InternalNode::ALL
+        .iter()
+        .find(|op| format!("{:?}", op) == symbol)
+    {
+        return Ok(NodeType::Internal(*internal));
+    }
+    if let Some(leaf) = LeafNode::ALL
+        .iter()
+        .find(|leaf| format!("{:?}", leaf) == symbol)
+    {
+        return Ok(NodeType::Leaf(*leaf));
+    }
+    if let Ok(value) = symbol.parse::<f32>() {
+        return Ok(NodeType::Leaf(LeafNode::Constant(value)));
+    }
+    Err(ParseError::UnknownSymbol {
+        symbol: symbol.to_string(),
+        position,
+    })
+}
+
+//splits on whitespace and parentheses, keeping the offset of every token
+struct Tokens<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(input: &'a str) -> Tokens<'a> {
+        Tokens { input, position: 0 }
+    }
+    fn peek(&self) -> Option<(&'a str, usize)> {
+        let rest = &self.input[self.position..];
+        let start = self.position + (rest.len() - rest.trim_start().len());
+        let rest = &self.input[start..];
+        let first = rest.chars().next()?;
+        if first == '(' || first == ')' {
+            return Some((&rest[..1], start));
+        }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(rest.len());
+        Some((&rest[..end], start))
+    }
+    fn next(&mut self) -> Option<(&'a str, usize)> {
+        let (token, start) = self.peek()?;
+        self.position = start + token.len();
+        Some((token, start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::genetic_ai::Method;
+
+    #[test]
+    fn round_trips_every_primitive() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        for _ in 0..50 {
+            let tree = Tree::new(5, Method::Grow, &mut rng);
+            let parsed = Tree::from_sexpr(&tree.to_sexpr()).unwrap();
+            assert_eq!(parsed.to_sexpr(), tree.to_sexpr());
+        }
+        for op in InternalNode::ALL {
+            for leaf in LeafNode::ALL {
+                let args = vec![format!("{:?}", leaf); op.arity()].join("\n  ");
+                let text = format!("({:?} {})", op, args);
+                let tree = Tree::from_sexpr(&text).unwrap();
+                assert_eq!(tree.root.node_type, NodeType::Internal(op));
+                assert_eq!(tree.subtree(1).node_type, NodeType::Leaf(leaf));
+            }
+        }
+        assert_eq!(
+            Tree::from_sexpr("(Sub Random AppleDistance)")
+                .unwrap()
+                .to_sexpr(),
+            "(Sub Random AppleDistance)"
+        );
+        let constants = Tree::from_sexpr("(Mul -1.5 (Add Row 0.1))").unwrap();
+        assert_eq!(
+            constants.subtree(1).node_type,
+            NodeType::Leaf(LeafNode::Constant(-1.5))
+        );
+        assert_eq!(constants.to_sexpr(), "(Mul -1.5 (Add Row 0.1))");
+    }
+
+    #[test]
+    fn reports_malformed_input() {
+        let error = |text| Tree::from_sexpr(text).unwrap_err().to_string();
+        assert_eq!(error(""), "unexpected end of input");
+        assert_eq!(error("(Add Row"), "unexpected end of input");
+        assert_eq!(error(")"), "unexpected `)` at 0");
+        assert_eq!(error("((Add Row Row) Row)"), "unexpected `(` at 1");
+        assert_eq!(error("(Add Row Apple)"), "unknown symbol `Apple` at 9");
+        assert_eq!(
+            error("(Add Row Column Row)"),
+            "`Add` at 1 takes 2 arguments but was given 3"
+        );
+        assert_eq!(
+            error("(Row Column)"),
+            "`Row` at 1 is a leaf and cannot be applied to arguments"
+        );
+        assert_eq!(error("Max"), "`Max` at 0 takes 2 arguments but was given 0");
+        assert_eq!(
+            error("(IfLessThan Row Column Row)"),
+            "`IfLessThan` at 1 takes 4 arguments but was given 3"
+        );
+        assert_eq!(error("Row Column"), "unexpected input after the tree at 4");
+    }
+}
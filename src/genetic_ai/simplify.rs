@@ -0,0 +1,188 @@
+use super::{InternalNode, LeafNode, Node, NodeType, Tree};
+
+impl Tree {
+    //an equivalent tree with constant subexpressions folded and identities removed.
+    //every `Random` leaf is kept in its original order so evaluation draws the same
+    //numbers, and only subtrees without one are ever dropped. values are assumed to be
+    //finite, which `TreePolicy` already insists on
+    pub fn simplify(&self) -> Tree {
+        Tree {
+            root: simplify(&self.root),
+        }
+    }
+}
+
+fn simplify(node: &Node) -> Node {
+    let NodeType::Internal(op) = node.node_type else {
+        return node.clone();
+    };
+    let args: Vec<Node> = node.children.iter().map(simplify).collect();
+    let constants: Option<Vec<f32>> = args.iter().map(constant).collect();
+    if let Some(value) = constants.and_then(|values| op.fold(&values)) {
+        return literal(value);
+    }
+    match (op, args.as_slice()) {
+        (InternalNode::Add, [left, right]) if constant(left) == Some(0.0) => right.clone(),
+        (InternalNode::Add | InternalNode::Sub, [left, right]) if constant(right) == Some(0.0) => {
+            left.clone()
+        }
+        (InternalNode::Sub, [left, right]) if same(left, right) => literal(0.0),
+        (InternalNode::Mul, [left, right]) if constant(left) == Some(1.0) => right.clone(),
+        (InternalNode::Mul, [left, right]) if constant(right) == Some(1.0) => left.clone(),
+        (InternalNode::Mul, [left, right])
+            if (constant(left) == Some(0.0) && is_pure(right))
+                || (constant(right) == Some(0.0) && is_pure(left)) =>
+        {
+            literal(0.0)
+        }
+        //dividing by zero returns the numerator
+        (InternalNode::Div, [left, right])
+            if constant(right) == Some(0.0) || constant(right) == Some(1.0) =>
+        {
+            left.clone()
+        }
+        (InternalNode::Div, [left, right]) if constant(left) == Some(0.0) && is_pure(right) => {
+            literal(0.0)
+        }
+        (InternalNode::Max | InternalNode::Min, [left, right]) if same(left, right) => left.clone(),
+        (InternalNode::Neg, [child])
+            if child.node_type == NodeType::Internal(InternalNode::Neg) =>
+        {
+            child.children[0].clone()
+        }
+        (InternalNode::Abs, [child])
+            if matches!(
+                child.node_type,
+                NodeType::Internal(InternalNode::Abs | InternalNode::Neg)
+            ) =>
+        {
+            Node::with_children(node.node_type.clone(), child.children.clone())
+        }
+        //a comparison that always goes the same way leaves the other branch dead
+        (InternalNode::IfLessThan, [a, b, then, otherwise]) => {
+            let taken = match (constant(a), constant(b)) {
+                (Some(a), Some(b)) => Some(a < b),
+                _ if same(a, b) => Some(false),
+                _ => None,
+            };
+            match taken {
+                Some(true) if is_pure(a) && is_pure(b) && is_pure(otherwise) => then.clone(),
+                Some(false) if is_pure(a) && is_pure(b) && is_pure(then) => otherwise.clone(),
+                _ if same(then, otherwise) && is_pure(a) && is_pure(b) => then.clone(),
+                _ => Node::with_children(node.node_type.clone(), args),
+            }
+        }
+        (InternalNode::IfDanger, [then, otherwise]) if same(then, otherwise) => then.clone(),
+        _ => Node::with_children(node.node_type.clone(), args),
+    }
+}
+
+//identical subtrees that are guaranteed to evaluate to the same value
+fn same(first: &Node, second: &Node) -> bool {
+    first == second && is_pure(first)
+}
+
+fn constant(node: &Node) -> Option<f32> {
+    match node.node_type {
+        NodeType::Leaf(LeafNode::Constant(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn literal(value: f32) -> Node {
+    Node::new(NodeType::Leaf(LeafNode::Constant(value)))
+}
+
+//evaluates the same every time on the same state
+fn is_pure(node: &Node) -> bool {
+    node.node_type != NodeType::Leaf(LeafNode::Random) && node.children().all(is_pure)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        genetic_ai::{Agent, Method},
+        gym::{
+            environment::{Environment, Policy},
+            game::{Game, GameConfig},
+        },
+    };
+
+    fn simplified(text: &str) -> String {
+        Tree::from_sexpr(text).unwrap().simplify().to_sexpr()
+    }
+
+    #[test]
+    fn removes_identities_and_folds_constants() {
+        assert_eq!(simplified("(Sub Row Row)"), "0");
+        assert_eq!(simplified("(Add (Sub Row Row) Column)"), "Column");
+        assert_eq!(simplified("(Max (Min Row Row) Row)"), "Row");
+        assert_eq!(simplified("(Mul Column (Add 0.5 0.5))"), "Column");
+        assert_eq!(simplified("(Div Column (Sub Row Row))"), "Column");
+        assert_eq!(simplified("(Mul (Sub Row Row) AppleDistance)"), "0");
+        assert_eq!(simplified("(Div 3 (Sub Column Column))"), "3");
+        //randomness has to be kept even when it cannot affect the value
+        assert_eq!(simplified("(Max Random Random)"), "(Max Random Random)");
+        assert_eq!(simplified("(Mul (Sub Row Row) Random)"), "(Mul 0 Random)");
+        assert_eq!(simplified("(Neg (Neg Row))"), "Row");
+        assert_eq!(simplified("(Abs (Neg Row))"), "(Abs Row)");
+        assert_eq!(simplified("(Sigmoid 0)"), "0.5");
+        //dead branches
+        assert_eq!(simplified("(IfLessThan 1 2 Row Column)"), "Row");
+        assert_eq!(simplified("(IfLessThan Row Row Row Column)"), "Column");
+        assert_eq!(simplified("(IfLessThan Row 2 Column Column)"), "Column");
+        assert_eq!(simplified("(IfDanger Row (Max Row Row))"), "Row");
+        assert_eq!(
+            simplified("(IfLessThan 1 2 Row Random)"),
+            "(IfLessThan 1 2 Row Random)"
+        );
+    }
+
+    #[test]
+    fn simplified_trees_pick_the_same_moves() {
+        //states visited by a handful of random programs
+        let mut rng = ChaCha8Rng::seed_from_u64(8);
+        let mut states = Vec::new();
+        for seed in 0..10 {
+            let driver = Agent::new(4, Method::Grow, &mut rng);
+            let mut game = Game::with_config(GameConfig::new().seed(seed).step_limit(60));
+            Environment::reset(&mut game);
+            let mut decisions = ChaCha8Rng::seed_from_u64(seed);
+            while !game.lost {
+                states.push(game.clone());
+                let action = driver.policy(&mut decisions).act(&game);
+                game.step(action);
+            }
+        }
+        assert!(states.len() > 100);
+
+        let mut shrunk = 0;
+        for index in 0..100 {
+            let method = if index % 2 == 0 {
+                Method::Grow
+            } else {
+                Method::Full
+            };
+            let original = Agent::new(5, method, &mut rng);
+            let simple = Agent::from_gene(original.gene.simplify());
+            shrunk += (simple.gene.size() < original.gene.size()) as usize;
+
+            let mut first = ChaCha8Rng::seed_from_u64(index);
+            let mut second = ChaCha8Rng::seed_from_u64(index);
+            for state in &states {
+                assert_eq!(
+                    original.policy(&mut first).act(state),
+                    simple.policy(&mut second).act(state),
+                    "{} simplified to {}",
+                    original.gene.to_sexpr(),
+                    simple.gene.to_sexpr()
+                );
+            }
+        }
+        assert!(shrunk > 0);
+    }
+}
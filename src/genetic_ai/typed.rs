@@ -0,0 +1,687 @@
+use std::fmt::Display;
+
+use rand::Rng;
+
+use super::{
+    crossover::CrossoverConfig,
+    mutation::{perturb, Mutation, MutationConfig},
+    InternalNode, LeafNode, Method, Node, NodeType,
+};
+use crate::gym::{environment::Policy, game::Game, snake::Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Float,
+    Bool,
+    Direction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Float(f32),
+    Bool(bool),
+    Direction(Direction),
+}
+
+//trees are type checked when built, so asking a value for the wrong type is a bug
+impl Value {
+    pub fn float(self) -> f32 {
+        match self {
+            Value::Float(value) => value,
+            other => panic!("expected a float, found {:?}", other),
+        }
+    }
+    pub fn bool(self) -> bool {
+        match self {
+            Value::Bool(value) => value,
+            other => panic!("expected a bool, found {:?}", other),
+        }
+    }
+    pub fn direction(self) -> Direction {
+        match self {
+            Value::Direction(value) => value,
+            other => panic!("expected a direction, found {:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    //the untyped terminals and functions, all of them floats
+    Leaf(LeafNode),
+    Arithmetic(InternalNode),
+    //(LessThan Float Float)
+    LessThan,
+    And,
+    Or,
+    Not,
+    //True or False
+    Constant(bool),
+    //carrying on straight is fatal
+    Danger,
+    //(Safe Direction), the neighbouring cell that way is not deadly
+    Safe,
+    //(AppleToward Direction), the nearest apple lies somewhere that way
+    AppleToward,
+    //(Distance Direction), steps to the nearest obstacle that way
+    Distance,
+    Heading,
+    Absolute(Direction),
+    //the direction closing the larger gap to the nearest apple
+    ToApple,
+    //(TurnLeft Direction)
+    TurnLeft,
+    TurnRight,
+    //(If Bool then else) for any type
+    If(Type),
+}
+
+impl Primitive {
+    pub fn output(&self) -> Type {
+        match self {
+            Primitive::Leaf(_) | Primitive::Arithmetic(_) | Primitive::Distance => Type::Float,
+            Primitive::LessThan
+            | Primitive::And
+            | Primitive::Or
+            | Primitive::Not
+            | Primitive::Constant(_)
+            | Primitive::Danger
+            | Primitive::Safe
+            | Primitive::AppleToward => Type::Bool,
+            Primitive::Heading
+            | Primitive::Absolute(_)
+            | Primitive::ToApple
+            | Primitive::TurnLeft
+            | Primitive::TurnRight => Type::Direction,
+            Primitive::If(output) => *output,
+        }
+    }
+    pub fn inputs(&self) -> Vec<Type> {
+        match self {
+            Primitive::Arithmetic(op) => vec![Type::Float; op.arity()],
+            Primitive::LessThan => vec![Type::Float, Type::Float],
+            Primitive::And | Primitive::Or => vec![Type::Bool, Type::Bool],
+            Primitive::Not => vec![Type::Bool],
+            Primitive::Safe
+            | Primitive::AppleToward
+            | Primitive::Distance
+            | Primitive::TurnLeft
+            | Primitive::TurnRight => vec![Type::Direction],
+            Primitive::If(output) => vec![Type::Bool, *output, *output],
+            Primitive::Leaf(_)
+            | Primitive::Constant(_)
+            | Primitive::Danger
+            | Primitive::Heading
+            | Primitive::Absolute(_)
+            | Primitive::ToApple => Vec::new(),
+        }
+    }
+    //every primitive with arguments that produces `output`
+    pub fn functions(output: Type) -> Vec<Primitive> {
+        match output {
+            Type::Float => InternalNode::ALL
+                .into_iter()
+                .map(Primitive::Arithmetic)
+                .chain([Primitive::Distance, Primitive::If(Type::Float)])
+                .collect(),
+            Type::Bool => vec![
+                Primitive::LessThan,
+                Primitive::And,
+                Primitive::Or,
+                Primitive::Not,
+                Primitive::Safe,
+                Primitive::AppleToward,
+                Primitive::If(Type::Bool),
+            ],
+            Type::Direction => vec![
+                Primitive::TurnLeft,
+                Primitive::TurnRight,
+                Primitive::If(Type::Direction),
+            ],
+        }
+    }
+    pub fn random_terminal<R: Rng + ?Sized>(output: Type, rng: &mut R) -> Primitive {
+        match output {
+            Type::Float => Primitive::Leaf(rng.gen()),
+            Type::Bool => match rng.gen_range(0..3) {
+                0 => Primitive::Danger,
+                n => Primitive::Constant(n == 1),
+            },
+            Type::Direction => match rng.gen_range(0..6) {
+                0 => Primitive::Heading,
+                1 => Primitive::ToApple,
+                n => Primitive::Absolute(Direction::ALL[n - 2]),
+            },
+        }
+    }
+    fn random_function<R: Rng + ?Sized>(output: Type, rng: &mut R) -> Primitive {
+        let functions = Primitive::functions(output);
+        functions[rng.gen_range(0..functions.len())]
+    }
+}
+
+impl Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Primitive::Leaf(leaf) => write!(f, "{}", Node::new(NodeType::Leaf(*leaf))),
+            Primitive::Arithmetic(op) => write!(f, "{:?}", op),
+            Primitive::Absolute(direction) => write!(f, "{:?}", direction),
+            Primitive::Constant(true) => write!(f, "True"),
+            Primitive::Constant(false) => write!(f, "False"),
+            Primitive::If(_) => write!(f, "If"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedNode {
+    pub primitive: Primitive,
+    pub children: Vec<TypedNode>,
+}
+
+impl TypedNode {
+    pub fn new(primitive: Primitive, children: Vec<TypedNode>) -> TypedNode {
+        TypedNode {
+            primitive,
+            children,
+        }
+    }
+    pub fn output(&self) -> Type {
+        self.primitive.output()
+    }
+    pub fn size(&self) -> usize {
+        1 + self.children.iter().map(TypedNode::size).sum::<usize>()
+    }
+    pub fn depth(&self) -> u32 {
+        self.children
+            .iter()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+    //whether every child has the type its parent expects
+    pub fn is_well_typed(&self) -> bool {
+        let inputs = self.primitive.inputs();
+        inputs.len() == self.children.len()
+            && inputs
+                .iter()
+                .zip(&self.children)
+                .all(|(input, child)| *input == child.output() && child.is_well_typed())
+    }
+    //children are evaluated first and in order, as in the untyped trees
+    pub fn eval<R: Rng + ?Sized>(&self, state: &Game, rng: &mut R) -> Value {
+        let args: Vec<Value> = self
+            .children
+            .iter()
+            .map(|child| child.eval(state, rng))
+            .collect();
+        match self.primitive {
+            Primitive::Leaf(leaf) => Value::Float(leaf.get_value(state, rng)),
+            Primitive::Arithmetic(op) => {
+                let args: Vec<f32> = args.into_iter().map(Value::float).collect();
+                Value::Float(op.eval(&args, state))
+            }
+            Primitive::LessThan => Value::Bool(args[0].float() < args[1].float()),
+            Primitive::And => Value::Bool(args[0].bool() && args[1].bool()),
+            Primitive::Or => Value::Bool(args[0].bool() || args[1].bool()),
+            Primitive::Not => Value::Bool(!args[0].bool()),
+            Primitive::Constant(value) => Value::Bool(value),
+            Primitive::Danger => Value::Bool(state.danger_ahead()),
+            Primitive::Safe => {
+                let next = state.snake.head().step(args[0].direction());
+                Value::Bool(!state.is_deadly(&next))
+            }
+            Primitive::AppleToward => {
+                Value::Bool(match (apple_offset(state), args[0].direction()) {
+                    (None, _) => false,
+                    (Some((_, y)), Direction::Up) => y < 0,
+                    (Some((_, y)), Direction::Down) => y > 0,
+                    (Some((x, _)), Direction::Left) => x < 0,
+                    (Some((x, _)), Direction::Right) => x > 0,
+                })
+            }
+            Primitive::Distance => {
+                Value::Float(state.obstacle_distance(args[0].direction()) as f32)
+            }
+            Primitive::Heading => Value::Direction(state.snake.direction),
+            Primitive::Absolute(direction) => Value::Direction(direction),
+            Primitive::ToApple => Value::Direction(match apple_offset(state) {
+                Some((x, y)) if x != 0 && x.abs() >= y.abs() => {
+                    if x > 0 {
+                        Direction::Right
+                    } else {
+                        Direction::Left
+                    }
+                }
+                Some((_, y)) if y != 0 => {
+                    if y > 0 {
+                        Direction::Down
+                    } else {
+                        Direction::Up
+                    }
+                }
+                _ => state.snake.direction,
+            }),
+            Primitive::TurnLeft => Value::Direction(args[0].direction().turn_left()),
+            Primitive::TurnRight => Value::Direction(args[0].direction().turn_right()),
+            Primitive::If(_) => {
+                if args[0].bool() {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
+        }
+    }
+    fn collect<'a>(&'a self, nodes: &mut Vec<&'a TypedNode>) {
+        nodes.push(self);
+        for child in &self.children {
+            child.collect(nodes);
+        }
+    }
+    fn constants_mut<'a>(&'a mut self, constants: &mut Vec<&'a mut f32>) {
+        if let Primitive::Leaf(LeafNode::Constant(value)) = &mut self.primitive {
+            constants.push(value);
+        }
+        for child in &mut self.children {
+            child.constants_mut(constants);
+        }
+    }
+    fn find_mut(&mut self, remaining: &mut usize) -> Option<&mut TypedNode> {
+        if *remaining == 0 {
+            return Some(self);
+        }
+        *remaining -= 1;
+        for child in &mut self.children {
+            if let Some(found) = child.find_mut(remaining) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+//how far the nearest apple is from the head along each axis
+fn apple_offset(state: &Game) -> Option<(i32, i32)> {
+    let head = state.snake.head();
+    state
+        .nearest_apple()
+        .map(|apple| (apple.location.x - head.x, apple.location.y - head.y))
+}
+
+impl Display for TypedNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.children.is_empty() {
+            return write!(f, "{}", self.primitive);
+        }
+        write!(f, "({}", self.primitive)?;
+        for child in &self.children {
+            write!(f, " {}", child)?;
+        }
+        write!(f, ")")
+    }
+}
+
+//a strongly typed counterpart to `Tree`, where every node declares what it returns
+//and what its children must return
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedTree {
+    pub root: TypedNode,
+}
+
+impl TypedTree {
+    pub fn new<R: Rng + ?Sized>(
+        output: Type,
+        depth_limit: u32,
+        method: Method,
+        rng: &mut R,
+    ) -> TypedTree {
+        TypedTree {
+            root: TypedTree::generate(output, depth_limit, 0, method, rng),
+        }
+    }
+    //grow and full, choosing only primitives that return the type asked for
+    fn generate<R: Rng + ?Sized>(
+        output: Type,
+        depth_limit: u32,
+        curr_level: u32,
+        method: Method,
+        rng: &mut R,
+    ) -> TypedNode {
+        let branch = curr_level < depth_limit
+            && match method {
+                Method::Full => true,
+                Method::Grow => rng.gen::<f32>() < 0.5,
+            };
+        if !branch {
+            return TypedNode::new(Primitive::random_terminal(output, rng), Vec::new());
+        }
+        let primitive = Primitive::random_function(output, rng);
+        let children = primitive
+            .inputs()
+            .into_iter()
+            .map(|input| TypedTree::generate(input, depth_limit, curr_level + 1, method, rng))
+            .collect();
+        TypedNode::new(primitive, children)
+    }
+    pub fn output(&self) -> Type {
+        self.root.output()
+    }
+    pub fn size(&self) -> usize {
+        self.root.size()
+    }
+    pub fn depth(&self) -> u32 {
+        self.root.depth()
+    }
+    pub fn nodes(&self) -> Vec<&TypedNode> {
+        let mut nodes = Vec::new();
+        self.root.collect(&mut nodes);
+        nodes
+    }
+    //nodes are addressed by their position in a preorder walk, like `Tree`
+    pub fn subtree(&self, index: usize) -> &TypedNode {
+        self.nodes()[index]
+    }
+    pub fn subtree_mut(&mut self, index: usize) -> &mut TypedNode {
+        let mut remaining = index;
+        self.root
+            .find_mut(&mut remaining)
+            .expect("subtree index out of range")
+    }
+    pub fn eval<R: Rng + ?Sized>(&self, state: &Game, rng: &mut R) -> Value {
+        self.root.eval(state, rng)
+    }
+    pub fn policy<'a, R: Rng + ?Sized>(&'a self, rng: &'a mut R) -> TypedPolicy<'a, R> {
+        TypedPolicy { tree: self, rng }
+    }
+    fn allows(&self, config: &CrossoverConfig) -> bool {
+        self.depth() <= config.max_depth && self.size() <= config.max_size
+    }
+}
+
+impl Display for TypedTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+//a direction tree's output is the move, float and bool trees score every state
+//reachable in one move like `TreePolicy`
+pub struct TypedPolicy<'a, R: Rng + ?Sized> {
+    pub tree: &'a TypedTree,
+    pub rng: &'a mut R,
+}
+
+impl<R: Rng + ?Sized> Policy<Game> for TypedPolicy<'_, R> {
+    fn act(&mut self, game: &Game) -> Direction {
+        if self.tree.output() == Type::Direction {
+            return self.tree.eval(game, self.rng).direction();
+        }
+        game.get_possible_states()
+            .iter()
+            .map(|(dir, state)| {
+                let score = match self.tree.eval(state, self.rng) {
+                    Value::Bool(value) => value as u8 as f32,
+                    value => value.float(),
+                };
+                (*dir, score)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap()
+            .0
+    }
+}
+
+fn pick<R: Rng + ?Sized>(candidates: &[usize], rng: &mut R) -> Option<usize> {
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[rng.gen_range(0..candidates.len())])
+}
+
+//subtree crossover that only swaps subtrees returning the same type, falling back to
+//a copy of the parent for any child over the limits
+pub fn typed_crossover<R: Rng + ?Sized>(
+    first: &TypedTree,
+    second: &TypedTree,
+    config: &CrossoverConfig,
+    rng: &mut R,
+) -> (TypedTree, TypedTree) {
+    let first_nodes = first.nodes();
+    let (leaves, internals): (Vec<usize>, Vec<usize>) =
+        (0..first_nodes.len()).partition(|index| first_nodes[*index].children.is_empty());
+    let internal = !internals.is_empty() && rng.gen::<f32>() < config.internal_bias;
+    let first_point = pick(if internal { &internals } else { &leaves }, rng).unwrap();
+    let output = first_nodes[first_point].output();
+
+    let matching: Vec<usize> = second
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.output() == output)
+        .map(|(index, _)| index)
+        .collect();
+    let Some(second_point) = pick(&matching, rng) else {
+        return (first.clone(), second.clone());
+    };
+
+    let mut first_child = first.clone();
+    *first_child.subtree_mut(first_point) = second.subtree(second_point).clone();
+    let mut second_child = second.clone();
+    *second_child.subtree_mut(second_point) = first.subtree(first_point).clone();
+    if !first_child.allows(config) {
+        first_child = first.clone();
+    }
+    if !second_child.allows(config) {
+        second_child = second.clone();
+    }
+    (first_child, second_child)
+}
+
+//the operators from `mutation`, each keeping every node's type intact
+pub fn typed_mutate<R: Rng + ?Sized>(tree: &mut TypedTree, config: &MutationConfig, rng: &mut R) {
+    match config.pick(rng) {
+        Some(Mutation::Subtree) => {
+            let point = rng.gen_range(0..tree.size());
+            let output = tree.subtree(point).output();
+            let grown = TypedTree::generate(output, config.subtree_depth, 0, Method::Grow, rng);
+            let replaced = std::mem::replace(tree.subtree_mut(point), grown);
+            if tree.depth() > config.max_depth {
+                *tree.subtree_mut(point) = replaced;
+            }
+        }
+        Some(Mutation::Point) => {
+            let point = rng.gen_range(0..tree.size());
+            let node = tree.subtree_mut(point);
+            let inputs = node.primitive.inputs();
+            let candidates: Vec<Primitive> = match node.primitive {
+                Primitive::Leaf(leaf) => vec![Primitive::Leaf(loop {
+                    let resampled: LeafNode = rng.gen();
+                    if resampled != leaf {
+                        break resampled;
+                    }
+                })],
+                //every type has at least two terminals to choose from
+                _ if inputs.is_empty() => vec![loop {
+                    let resampled = Primitive::random_terminal(node.output(), rng);
+                    if resampled != node.primitive {
+                        break resampled;
+                    }
+                }],
+                _ => Primitive::functions(node.output()),
+            };
+            let candidates: Vec<Primitive> = candidates
+                .into_iter()
+                .filter(|p| *p != node.primitive && p.inputs() == inputs)
+                .collect();
+            if !candidates.is_empty() {
+                node.primitive = candidates[rng.gen_range(0..candidates.len())];
+            }
+        }
+        Some(Mutation::Hoist) => {
+            let output = tree.output();
+            let nodes = tree.nodes();
+            let matching: Vec<usize> = (1..nodes.len())
+                .filter(|index| nodes[*index].output() == output)
+                .collect();
+            if let Some(point) = pick(&matching, rng) {
+                tree.root = tree.subtree(point).clone();
+            }
+        }
+        Some(Mutation::Shrink) => {
+            let nodes = tree.nodes();
+            let shrinkable: Vec<usize> = (0..nodes.len())
+                .filter(|index| {
+                    let node = nodes[*index];
+                    node.children.iter().any(|c| c.output() == node.output())
+                })
+                .collect();
+            if let Some(point) = pick(&shrinkable, rng) {
+                let node = tree.subtree_mut(point);
+                let output = node.output();
+                let children: Vec<usize> = (0..node.children.len())
+                    .filter(|index| node.children[*index].output() == output)
+                    .collect();
+                let child = pick(&children, rng).unwrap();
+                *node = node.children.swap_remove(child);
+            }
+        }
+        Some(Mutation::Constant) => {
+            let mut constants = Vec::new();
+            tree.root.constants_mut(&mut constants);
+            perturb(constants, config.constant_sigma, rng);
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{genetic_ai::evaluation::play_episode, gym::game::GameConfig};
+
+    fn random_trees(rng: &mut ChaCha8Rng) -> Vec<TypedTree> {
+        let mut trees = Vec::new();
+        for output in [Type::Float, Type::Bool, Type::Direction] {
+            for method in [Method::Grow, Method::Full] {
+                for _ in 0..20 {
+                    trees.push(TypedTree::new(output, 4, method, rng));
+                }
+            }
+        }
+        trees
+    }
+
+    #[test]
+    fn generation_respects_types() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for output in [Type::Float, Type::Bool, Type::Direction] {
+            for _ in 0..50 {
+                let grown = TypedTree::new(output, 4, Method::Grow, &mut rng);
+                assert_eq!(grown.output(), output);
+                assert!(grown.root.is_well_typed(), "{}", grown);
+                assert!(grown.depth() <= 4);
+
+                let full = TypedTree::new(output, 4, Method::Full, &mut rng);
+                assert!(full.root.is_well_typed(), "{}", full);
+                assert_eq!(full.depth(), 4);
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_and_mutation_keep_trees_well_typed() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let trees = random_trees(&mut rng);
+        let config = MutationConfig {
+            subtree: 0.2,
+            point: 0.2,
+            hoist: 0.2,
+            shrink: 0.2,
+            constant: 0.2,
+            ..MutationConfig::default()
+        };
+        for _ in 0..500 {
+            let first = &trees[rng.gen_range(0..trees.len())];
+            let second = &trees[rng.gen_range(0..trees.len())];
+            let (mut a, mut b) =
+                typed_crossover(first, second, &CrossoverConfig::default(), &mut rng);
+            typed_mutate(&mut a, &config, &mut rng);
+            typed_mutate(&mut b, &config, &mut rng);
+            assert_eq!(a.output(), first.output());
+            assert_eq!(b.output(), second.output());
+            assert!(a.root.is_well_typed(), "{}", a);
+            assert!(b.root.is_well_typed(), "{}", b);
+        }
+    }
+
+    #[test]
+    fn point_mutation_changes_bool_leaves() {
+        let config = MutationConfig {
+            subtree: 0.0,
+            point: 1.0,
+            hoist: 0.0,
+            shrink: 0.0,
+            constant: 0.0,
+            ..MutationConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut tree = TypedTree {
+            root: TypedNode::new(Primitive::Danger, Vec::new()),
+        };
+        let mut seen = Vec::new();
+        for _ in 0..20 {
+            let before = tree.root.primitive;
+            typed_mutate(&mut tree, &config, &mut rng);
+            assert_ne!(tree.root.primitive, before);
+            assert_eq!(tree.output(), Type::Bool);
+            if !seen.contains(&tree.to_string()) {
+                seen.push(tree.to_string());
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, vec!["Danger", "False", "True"]);
+    }
+
+    #[test]
+    fn direction_trees_choose_the_move() {
+        let leaf = |p| TypedNode::new(p, Vec::new());
+        let node = TypedNode::new;
+        //head for the apple when that is safe, otherwise turn whichever way is safe
+        let tree = TypedTree {
+            root: node(
+                Primitive::If(Type::Direction),
+                vec![
+                    node(Primitive::Safe, vec![leaf(Primitive::ToApple)]),
+                    leaf(Primitive::ToApple),
+                    node(
+                        Primitive::If(Type::Direction),
+                        vec![
+                            node(
+                                Primitive::Safe,
+                                vec![node(Primitive::TurnLeft, vec![leaf(Primitive::Heading)])],
+                            ),
+                            node(Primitive::TurnLeft, vec![leaf(Primitive::Heading)]),
+                            node(Primitive::TurnRight, vec![leaf(Primitive::Heading)]),
+                        ],
+                    ),
+                ],
+            ),
+        };
+        assert!(tree.root.is_well_typed());
+        assert_eq!(
+            tree.to_string(),
+            "(If (Safe ToApple) ToApple (If (Safe (TurnLeft Heading)) \
+             (TurnLeft Heading) (TurnRight Heading)))"
+        );
+
+        let mut game = Game::with_config(GameConfig::new().step_limit(200));
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let episode = play_episode(&mut game, &mut tree.policy(&mut rng), 3, 100);
+        assert!(episode.apples >= 3, "{:?}", episode);
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::ThreadPool;
+
+use crate::gym::game::{Game, GameConfig};
+
+use super::{
+    evaluation::{play_episode, Episode},
+    population::{
+        decisions, evaluate_each, score, EvaluationConfig, EvolutionConfig, GenerationStats,
+    },
+    selection::Scored,
+    typed::{typed_crossover, typed_mutate, Type, TypedTree},
+    Method,
+};
+
+#[derive(Debug, Clone)]
+pub struct TypedAgent {
+    pub fitness: f32,
+    pub cases: Vec<f32>,
+    pub episodes: Vec<Episode>,
+    pub gene: TypedTree,
+}
+
+impl TypedAgent {
+    pub fn from_gene(gene: TypedTree) -> TypedAgent {
+        TypedAgent {
+            fitness: 0.0,
+            cases: Vec::new(),
+            episodes: Vec::new(),
+            gene,
+        }
+    }
+}
+
+impl Scored for TypedAgent {
+    fn fitness(&self) -> f32 {
+        self.fitness
+    }
+    fn cases(&self) -> &[f32] {
+        &self.cases
+    }
+}
+
+//`Population` for typed trees that all return `output`, bred with the type respecting
+//operators and evaluated the same way
+#[derive(Clone)]
+pub struct TypedPopulation {
+    pub individuals: Vec<TypedAgent>,
+    pub output: Type,
+    pub rng: ChaCha8Rng,
+    pub generation: u32,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl TypedPopulation {
+    pub fn new(size: u32, depth_limit: u32, output: Type, seed: u64) -> TypedPopulation {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let individuals = (0..size)
+            .map(|index| {
+                let method = if index < size / 2 {
+                    Method::Grow
+                } else {
+                    Method::Full
+                };
+                TypedAgent::from_gene(TypedTree::new(output, depth_limit, method, &mut rng))
+            })
+            .collect();
+        TypedPopulation {
+            individuals,
+            output,
+            rng,
+            generation: 0,
+            pool: None,
+        }
+    }
+    pub fn evaluate(&mut self, game_config: &GameConfig, config: &EvaluationConfig) {
+        let seeds: Vec<u64> = (0..config.episodes).map(|_| self.rng.gen()).collect();
+        evaluate_each(
+            &mut self.individuals,
+            &mut self.pool,
+            game_config,
+            config,
+            |individual, game| evaluate_individual(individual, game, &seeds, config),
+        );
+    }
+    pub fn evolve(
+        &mut self,
+        game_config: &GameConfig,
+        generations: u32,
+        config: &EvolutionConfig,
+    ) -> Vec<GenerationStats> {
+        let mut history = Vec::new();
+        for remaining in (0..generations).rev() {
+            self.evaluate(game_config, &config.evaluation);
+            history.push(self.stats(self.generation));
+            if remaining > 0 {
+                self.next_generation(config);
+            }
+        }
+        history
+    }
+    pub fn stats(&self, generation: u32) -> GenerationStats {
+        GenerationStats::new(
+            generation,
+            self.individuals.iter().map(|a| a.fitness).collect(),
+        )
+    }
+    pub fn next_generation(&mut self, config: &EvolutionConfig) {
+        let size = self.individuals.len();
+        let mut ranked: Vec<usize> = (0..size).collect();
+        ranked.sort_by(|a, b| {
+            self.individuals[*b]
+                .fitness
+                .total_cmp(&self.individuals[*a].fitness)
+        });
+
+        let mut next: Vec<TypedAgent> = ranked
+            .iter()
+            .take(config.elites.min(size))
+            .map(|index| self.individuals[*index].clone())
+            .collect();
+        let selector = config.selection.prepare(&self.individuals);
+        while next.len() < size {
+            let first = &self.individuals[selector.select(&mut self.rng)].gene;
+            let second = &self.individuals[selector.select(&mut self.rng)].gene;
+            let (mut first, mut second) = if self.rng.gen::<f32>() < config.crossover_rate {
+                typed_crossover(first, second, &config.crossover, &mut self.rng)
+            } else {
+                (first.clone(), second.clone())
+            };
+            typed_mutate(&mut first, &config.mutation, &mut self.rng);
+            typed_mutate(&mut second, &config.mutation, &mut self.rng);
+            next.push(TypedAgent::from_gene(first));
+            if next.len() < size {
+                next.push(TypedAgent::from_gene(second));
+            }
+        }
+        self.individuals = next;
+        self.generation += 1;
+    }
+    pub fn return_best(&self) -> &TypedAgent {
+        self.individuals
+            .iter()
+            .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+            .unwrap()
+    }
+}
+
+fn evaluate_individual(
+    individual: &mut TypedAgent,
+    game: &mut Game,
+    seeds: &[u64],
+    config: &EvaluationConfig,
+) {
+    let episodes: Vec<Episode> = seeds
+        .iter()
+        .map(|seed| {
+            let mut decisions = decisions(*seed);
+            let mut policy = individual.gene.policy(&mut decisions);
+            play_episode(game, &mut policy, *seed, config.starvation_limit)
+        })
+        .collect();
+    (individual.cases, individual.fitness) = score(&episodes, config);
+    individual.episodes = episodes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_trees_evolve() {
+        let game = GameConfig::new().size(8, 8).step_limit(150);
+        let evolve = |seed| {
+            let mut population = TypedPopulation::new(12, 3, Type::Direction, seed);
+            let history = population.evolve(&game, 4, &EvolutionConfig::default());
+            assert!(population
+                .individuals
+                .iter()
+                .all(|a| a.gene.output() == Type::Direction && a.gene.root.is_well_typed()));
+            (history, population.return_best().gene.to_string())
+        };
+        let (history, best) = evolve(7);
+        assert_eq!(history.len(), 4);
+        assert!(history[3].best > 0.0);
+        assert_eq!(evolve(7), (history, best));
+    }
+}
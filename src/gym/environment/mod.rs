@@ -0,0 +1,256 @@
+use crate::gym::{
+    game::{ActionMode, Board, Game, Point},
+    snake::{Direction, Turn},
+};
+
+pub trait Environment {
+    type Observation;
+    type Action;
+
+    fn reset(&mut self) -> Self::Observation;
+    fn step(&mut self, action: Self::Action) -> StepResult<Self::Observation>;
+    fn observation(&self) -> Self::Observation;
+    fn action_space(&self) -> Space;
+    fn observation_space(&self) -> Space;
+}
+
+pub trait Policy<E: Environment> {
+    fn act(&mut self, env: &E) -> E::Action;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Space {
+    Discrete(usize),
+    Box {
+        shape: Vec<usize>,
+        low: f32,
+        high: f32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct StepResult<O> {
+    pub observation: O,
+    pub reward: f32,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Debug, Clone)]
+pub struct StepInfo {
+    pub score: u32,
+    pub steps: u32,
+    pub length: usize,
+    pub ate: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub board: Board,
+    pub head: Point,
+    pub direction: Direction,
+}
+
+impl Environment for Game {
+    type Observation = Observation;
+    type Action = Direction;
+
+    fn reset(&mut self) -> Observation {
+        Game::reset(self);
+        self.observation()
+    }
+    fn step(&mut self, action: Direction) -> StepResult<Observation> {
+        self.update_direction(action);
+        self.advance_step()
+    }
+    fn observation(&self) -> Observation {
+        Observation {
+            board: self.board.clone(),
+            head: self.snake.head(),
+            direction: self.snake.direction,
+        }
+    }
+    fn action_space(&self) -> Space {
+        Space::Discrete(Direction::ALL.len())
+    }
+    fn observation_space(&self) -> Space {
+        Space::Box {
+            shape: vec![self.config.height as usize, self.config.width as usize],
+            low: 0.0,
+            high: 2.0,
+        }
+    }
+}
+
+impl Game {
+    //moves once the direction has been set, shared by both action spaces
+    fn advance_step(&mut self) -> StepResult<Observation> {
+        let starting_score = self.score;
+        let ate = self.update();
+        StepResult {
+            observation: self.observation(),
+            reward: (self.score - starting_score) as f32,
+            done: self.lost,
+            info: StepInfo {
+                score: self.score,
+                steps: self.steps,
+                length: self.snake.len(),
+                ate,
+            },
+        }
+    }
+}
+
+//a game whose actions are turns relative to the way the snake is heading
+#[derive(Clone)]
+pub struct RelativeGame {
+    pub game: Game,
+}
+
+impl RelativeGame {
+    pub fn new(mut game: Game) -> RelativeGame {
+        game.config.actions = ActionMode::Relative;
+        RelativeGame { game }
+    }
+}
+
+impl Environment for RelativeGame {
+    type Observation = Observation;
+    type Action = Turn;
+
+    fn reset(&mut self) -> Observation {
+        Environment::reset(&mut self.game)
+    }
+    fn step(&mut self, action: Turn) -> StepResult<Observation> {
+        self.game.turn(action);
+        self.game.advance_step()
+    }
+    fn observation(&self) -> Observation {
+        self.game.observation()
+    }
+    fn action_space(&self) -> Space {
+        Space::Discrete(Turn::ALL.len())
+    }
+    fn observation_space(&self) -> Space {
+        self.game.observation_space()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::game::{GameConfig, Reversal};
+
+    #[test]
+    fn step_reports_reward_and_done() {
+        let mut game = Game::with_config(
+            GameConfig::new()
+                .size(5, 5)
+                .starting_position(0, 0)
+                .starting_direction(Direction::Right),
+        );
+        let observation = Environment::reset(&mut game);
+        assert_eq!(observation.board.state.len(), 5);
+        assert_eq!(game.action_space(), Space::Discrete(4));
+
+        let mut done = false;
+        let mut total_reward = 0.0;
+        for _ in 0..5 {
+            let result = game.step(Direction::Right);
+            total_reward += result.reward;
+            done = result.done;
+        }
+        assert!(done);
+        assert_eq!(total_reward, game.score as f32);
+    }
+
+    #[test]
+    fn relative_mode_never_reverses() {
+        let config = GameConfig::new()
+            .size(8, 3)
+            .starting_position(3, 1)
+            .starting_direction(Direction::Right)
+            .starting_length(3)
+            .apple_count(0)
+            .actions(ActionMode::Relative);
+        for reversal in [Reversal::Allowed, Reversal::Ignored, Reversal::Disallowed] {
+            let mut game = Game::with_config(config.clone().reversal(reversal));
+            Environment::reset(&mut game);
+            let result = game.step(Direction::Left);
+            assert!(!result.done, "{:?}", reversal);
+            assert_eq!(result.observation.head, Point { x: 4, y: 1 });
+
+            let result = game.step(Direction::Right.turn(Turn::Right));
+            assert!(!result.done);
+            assert_eq!(result.observation.direction, Direction::Down);
+        }
+    }
+
+    #[test]
+    fn relative_game_steps_with_turns() {
+        let config = GameConfig::new()
+            .size(7, 7)
+            .starting_position(3, 3)
+            .starting_direction(Direction::Up)
+            .apple_count(0);
+        for (turn, heading, head) in [
+            (Turn::Left, Direction::Left, Point { x: 2, y: 3 }),
+            (Turn::Straight, Direction::Up, Point { x: 3, y: 2 }),
+            (Turn::Right, Direction::Right, Point { x: 4, y: 3 }),
+        ] {
+            let mut env = RelativeGame::new(Game::with_config(config.clone()));
+            assert_eq!(env.action_space(), Space::Discrete(3));
+            env.reset();
+            let result = env.step(turn);
+            assert!(!result.done);
+            assert_eq!(result.observation.direction, heading);
+            assert_eq!(result.observation.head, head);
+        }
+
+        //turning right four times comes back to the start
+        let mut env = RelativeGame::new(Game::with_config(config));
+        env.reset();
+        for _ in 0..4 {
+            env.step(Turn::Right);
+        }
+        assert_eq!(env.observation().head, Point { x: 3, y: 3 });
+        assert_eq!(env.observation().direction, Direction::Up);
+    }
+
+    #[test]
+    fn biting_the_body_ends_the_same_step() {
+        let mut game = Game::with_config(
+            GameConfig::new()
+                .size(8, 8)
+                .starting_position(4, 2)
+                .starting_direction(Direction::Right)
+                .starting_length(5)
+                .apple_count(0),
+        );
+        Environment::reset(&mut game);
+        assert!(!game.step(Direction::Down).done);
+        assert!(!game.step(Direction::Left).done);
+        let result = game.step(Direction::Up);
+        assert!(result.done);
+        assert_eq!(result.info.steps, 2);
+    }
+
+    #[test]
+    fn eating_is_reported_without_a_score() {
+        let mut game = Game::with_config(
+            GameConfig::new()
+                .size(5, 1)
+                .starting_direction(Direction::Right)
+                .score_per_apple(0),
+        );
+        Environment::reset(&mut game);
+        let mut eaten = 0;
+        while !game.lost {
+            let result = game.step(Direction::Right);
+            eaten += result.info.ate as usize;
+            assert_eq!(result.reward, 0.0);
+        }
+        assert_eq!(eaten, game.snake.len() - 1);
+        assert!(eaten > 0);
+    }
+}
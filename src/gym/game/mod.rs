@@ -1,7 +1,123 @@
-use std::{borrow::BorrowMut, fmt::Display};
+use std::fmt::Display;
 
-use crate::gym::snake::{Direction, Snake};
+use std::sync::{Arc, Mutex};
+
+use crate::gym::{
+    render::SharedRenderer,
+    replay::{Replay, Spawn},
+    snake::{Direction, Snake, Turn},
+};
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameConfig {
+    pub width: i32,
+    pub height: i32,
+    pub starting_length: usize,
+    pub starting_position: Point,
+    pub starting_direction: Direction,
+    pub apple_count: usize,
+    pub score_per_apple: u32,
+    pub step_limit: Option<u32>,
+    pub seed: u64,
+    #[serde(default)]
+    pub actions: ActionMode,
+    #[serde(default)]
+    pub reversal: Reversal,
+}
+
+//which moves a learner picks between each step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionMode {
+    //any of the four directions
+    #[default]
+    Absolute,
+    //left, straight on or right of the way the snake is heading, anything else is
+    //treated as going straight on. `RelativeGame` steps with turns directly
+    Relative,
+}
+
+//what happens when the snake is told to turn back the way it came
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Reversal {
+    //it moves back into its neck, which only a snake of one or two cells survives
+    #[default]
+    Allowed,
+    //the snake keeps going the way it was
+    Ignored,
+    //the game is lost
+    Disallowed,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            width: 10,
+            height: 10,
+            starting_length: 1,
+            starting_position: Point { x: 0, y: 0 },
+            starting_direction: Direction::Down,
+            apple_count: 1,
+            score_per_apple: 5,
+            step_limit: None,
+            seed: 0,
+            actions: ActionMode::Absolute,
+            reversal: Reversal::Allowed,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new() -> Self {
+        GameConfig::default()
+    }
+    pub fn size(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+    pub fn starting_length(mut self, starting_length: usize) -> Self {
+        self.starting_length = starting_length;
+        self
+    }
+    pub fn starting_position(mut self, x: i32, y: i32) -> Self {
+        self.starting_position = Point { x, y };
+        self
+    }
+    pub fn starting_direction(mut self, direction: Direction) -> Self {
+        self.starting_direction = direction;
+        self
+    }
+    pub fn apple_count(mut self, apple_count: usize) -> Self {
+        self.apple_count = apple_count;
+        self
+    }
+    pub fn score_per_apple(mut self, score_per_apple: u32) -> Self {
+        self.score_per_apple = score_per_apple;
+        self
+    }
+    pub fn step_limit(mut self, step_limit: u32) -> Self {
+        self.step_limit = Some(step_limit);
+        self
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    pub fn actions(mut self, actions: ActionMode) -> Self {
+        self.actions = actions;
+        self
+    }
+    pub fn reversal(mut self, reversal: Reversal) -> Self {
+        self.reversal = reversal;
+        self
+    }
+    pub fn in_bounds(&self, point: &Point) -> bool {
+        point.x >= 0 && point.x < self.width && point.y >= 0 && point.y < self.height
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Board {
@@ -11,23 +127,26 @@ pub struct Board {
 }
 
 impl Board {
-    pub fn new(width: i32, height: i32, snake: &Snake, apple: &Apple) -> Self {
-        let mut state = Vec::new();
-        for _ in 0..height {
-            let mut row = Vec::new();
-            for _ in 0..width {
-                row.push(0);
-            }
-            state.push(row);
-        }
-        let head = snake.body.front().unwrap();
-        state[head.y as usize][head.x as usize] = 1;
-        state[apple.location.y as usize][apple.location.x as usize] = 2;
-        Board {
+    pub fn new(width: i32, height: i32, snake: &Snake, apples: &[Apple]) -> Self {
+        let mut board = Board {
             width,
             height,
-            state,
+            state: Vec::new(),
+        };
+        board.draw(snake, apples);
+        board
+    }
+    pub fn draw(&mut self, snake: &Snake, apples: &[Apple]) {
+        self.state = vec![vec![0; self.width as usize]; self.height as usize];
+        for point in snake.body() {
+            self.set(point, 1);
         }
+        for apple in apples {
+            self.set(&apple.location, 2);
+        }
+    }
+    pub fn set(&mut self, point: &Point, value: i32) {
+        self.state[point.y as usize][point.x as usize] = value;
     }
 }
 impl Display for Board {
@@ -42,12 +161,36 @@ impl Display for Board {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 }
 
+impl Point {
+    //the neighbouring cell in `direction`
+    pub fn step(&self, direction: Direction) -> Point {
+        match direction {
+            Direction::Up => Point {
+                x: self.x,
+                y: self.y - 1,
+            },
+            Direction::Down => Point {
+                x: self.x,
+                y: self.y + 1,
+            },
+            Direction::Left => Point {
+                x: self.x - 1,
+                y: self.y,
+            },
+            Direction::Right => Point {
+                x: self.x + 1,
+                y: self.y,
+            },
+        }
+    }
+}
+
 impl Display for Point {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {})", self.x, self.y)
@@ -56,143 +199,263 @@ impl Display for Point {
 
 #[derive(Clone)]
 pub struct Game {
+    pub config: GameConfig,
     pub snake: Snake,
     pub board: Board,
-    pub apple: Apple,
+    pub apples: Vec<Apple>,
     pub score: u32,
+    pub steps: u32,
     pub lost: bool,
+    pub rng: ChaCha8Rng,
+    pub renderer: Option<SharedRenderer>,
+    //shared rather than owned so candidate states stay cheap to clone
+    pub recording: Option<Arc<Mutex<Replay>>>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
-        let width = 10;
-        let height = 10;
-        let snake = Snake::new();
-        let apple = Apple::rand_apple(width, height);
-        Game {
-            board: Board::new(width, height, &snake, &apple),
+        Game::with_config(GameConfig::default())
+    }
+    pub fn with_config(config: GameConfig) -> Self {
+        let snake = Game::spawn_snake(&config);
+        let mut game = Game {
+            board: Board::new(config.width, config.height, &snake, &[]),
             snake,
-            apple,
+            apples: Vec::new(),
             score: 0,
+            steps: 0,
             lost: false,
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            renderer: None,
+            recording: None,
+            config,
+        };
+        game.spawn_apples();
+        game
+    }
+    fn spawn_snake(config: &GameConfig) -> Snake {
+        let snake = Snake::spawn(
+            config.starting_position,
+            config.starting_direction,
+            config.starting_length,
+            config.width,
+            config.height,
+        );
+        for point in snake.body() {
+            assert!(
+                config.in_bounds(point),
+                "starting snake does not fit on a {}x{} board",
+                config.width,
+                config.height
+            );
+        }
+        snake
+    }
+    fn spawn_apples(&mut self) {
+        while self.apples.len() < self.config.apple_count {
+            let free = self.free_cells();
+            if free.is_empty() {
+                break;
+            }
+            let apple = Apple::from_list(free, &mut self.rng);
+            self.board.set(&apple.location, 2);
+            if let Some(recording) = &self.recording {
+                recording.lock().unwrap().spawns.push(Spawn {
+                    step: self.steps,
+                    location: apple.location,
+                });
+            }
+            self.apples.push(apple);
         }
     }
+    pub fn free_cells(&self) -> Vec<Point> {
+        let mut free = Vec::new();
+        for (y, row) in self.board.state.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if *cell == 0 {
+                    free.push(Point {
+                        x: x as i32,
+                        y: y as i32,
+                    });
+                }
+            }
+        }
+        free
+    }
+    pub fn nearest_apple(&self) -> Option<&Apple> {
+        let head = self.snake.head();
+        self.apples.iter().min_by_key(|apple| {
+            (apple.location.x - head.x).abs() + (apple.location.y - head.y).abs()
+        })
+    }
+    pub fn next_head(&self) -> Point {
+        self.snake.head().step(self.snake.direction)
+    }
+    //walls and the body, the tail counts even though it may move out of the way
+    pub fn is_deadly(&self, point: &Point) -> bool {
+        !self.config.in_bounds(point) || self.snake.contains(point)
+    }
+    //whether carrying on straight ends the game, true if it already has
+    pub fn danger_ahead(&self) -> bool {
+        self.lost || self.is_deadly(&self.next_head())
+    }
+    //steps until the head would hit something going in `direction`, zero once the
+    //game is already lost
+    pub fn obstacle_distance(&self, direction: Direction) -> u32 {
+        if self.lost {
+            return 0;
+        }
+        let mut point = self.snake.head();
+        let mut distance = 0;
+        loop {
+            point = point.step(direction);
+            distance += 1;
+            if self.is_deadly(&point) {
+                return distance;
+            }
+        }
+    }
+    //free cells the head could still get to, found by flood fill
+    pub fn reachable_cells(&self) -> usize {
+        if self.lost {
+            return 0;
+        }
+        let width = self.config.width;
+        let mut seen = vec![false; (width * self.config.height) as usize];
+        let mut frontier = vec![self.snake.head()];
+        let mut count = 0;
+        while let Some(point) = frontier.pop() {
+            for direction in Direction::ALL {
+                let next = point.step(direction);
+                if self.is_deadly(&next) || seen[(next.y * width + next.x) as usize] {
+                    continue;
+                }
+                seen[(next.y * width + next.x) as usize] = true;
+                count += 1;
+                frontier.push(next);
+            }
+        }
+        count
+    }
     pub fn check_loss(&self, next: &Point) -> bool {
-        if next.x < 0 || next.x >= self.board.width {
+        if !self.config.in_bounds(next) {
             return true;
         }
-        if next.y < 0 || next.y >= self.board.height {
+        if self.config.reversal == Reversal::Disallowed && self.is_reversing() {
             return true;
         }
-        self.snake.check_self_collision()
-    }
-    pub fn check_eat(&mut self, next_point: &Point) {
-        if next_point.x == self.apple.location.x && next_point.y == self.apple.location.y {
-            let tail = self.snake.body.back().unwrap().clone();
-            self.snake.body.push_back(tail);
-            self.apple = Apple::rand_apple(self.board.width, self.board.height);
-            self.score += 5;
+        self.snake.bites(next)
+    }
+    pub fn check_eat(&mut self, next_point: &Point) -> bool {
+        let eaten = self
+            .apples
+            .iter()
+            .position(|apple| apple.location == *next_point);
+        match eaten {
+            Some(index) => {
+                self.apples.remove(index);
+                self.snake.grow();
+                self.score += self.config.score_per_apple;
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn update(&mut self) {
-        //check next square
-        let next: Point;
-        match self.snake.direction {
-            Direction::Up => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x,
-                    y: self.snake.body.front().unwrap().y - 1,
-                }
-            }
-            Direction::Down => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x,
-                    y: self.snake.body.front().unwrap().y + 1,
-                }
-            }
-            Direction::Left => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x - 1,
-                    y: self.snake.body.front().unwrap().y,
-                }
-            }
-            Direction::Right => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x + 1,
-                    y: self.snake.body.front().unwrap().y,
-                }
+    //returns whether an apple was eaten
+    pub fn update(&mut self) -> bool {
+        if let Some(recording) = &self.recording {
+            if !self.lost {
+                recording.lock().unwrap().record(self.snake.direction);
             }
         }
+        let ate = self.advance();
+        if let Some(renderer) = &self.renderer {
+            renderer.lock().unwrap().on_step(self);
+        }
+        ate
+    }
+
+    fn advance(&mut self) -> bool {
+        //check next square
+        let next = self.next_head();
 
         if self.check_loss(&next) {
             self.lost = true;
-            return;
+            return false;
         }
-        self.check_eat(&next);
-        self.snake.slither();
-        self.board.state = vec![vec![0; 10]; 10];
-        //draw snake
-        let snake = &self.snake.body.clone().into_iter().collect::<Vec<Point>>();
-        for i in 0..snake.len() {
-            self.board.state[snake[i].y as usize][snake[i].x as usize] = 1;
+        let ate = self.check_eat(&next);
+        self.slither();
+        if ate {
+            self.spawn_apples();
         }
-        //check if app inside snake
-        while self.board.state[self.apple.location.y as usize][self.apple.location.x as usize] == 1
-        {
-            self.apple = Apple::rand_apple(self.board.width, self.board.height);
+        self.steps += 1;
+        if let Some(limit) = self.config.step_limit {
+            if self.steps >= limit {
+                self.lost = true;
+            }
         }
-        self.board.state[self.apple.location.y as usize][self.apple.location.x as usize] = 2;
+        ate
     }
 
     pub fn test_update(&mut self) {
         //check next square
-        let next: Point;
-        match self.snake.direction {
-            Direction::Up => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x,
-                    y: self.snake.body.front().unwrap().y - 1,
-                }
-            }
-            Direction::Down => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x,
-                    y: self.snake.body.front().unwrap().y + 1,
-                }
-            }
-            Direction::Left => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x - 1,
-                    y: self.snake.body.front().unwrap().y,
-                }
-            }
-            Direction::Right => {
-                next = Point {
-                    x: self.snake.body.front().unwrap().x + 1,
-                    y: self.snake.body.front().unwrap().y,
-                }
-            }
-        }
+        let next = self.next_head();
 
         if self.check_loss(&next) {
             self.lost = true;
             return;
         }
 
+        self.slither();
+    }
+
+    fn slither(&mut self) {
+        //only the cells the head and tail touch need redrawing
+        let tail = self.snake.tail();
         self.snake.slither();
-        self.board.state = vec![vec![0; 10]; 10];
-        //draw snake
-        let snake = &self.snake.body.clone().into_iter().collect::<Vec<Point>>();
-        for i in 0..snake.len() {
-            self.board.state[snake[i].y as usize][snake[i].x as usize] = 1;
+        if !self.snake.contains(&tail) {
+            self.board.set(&tail, 0);
         }
-        //check if app inside snake
+        self.board.set(&self.snake.head(), 1);
     }
 
+    //a reversal is never a relative action, so in that mode it is ignored whatever
+    //`reversal` says
     pub fn update_direction(&mut self, direction: Direction) {
+        let ignored = self.config.reversal == Reversal::Ignored
+            || self.config.actions == ActionMode::Relative;
+        if ignored && direction == self.snake.heading().opposite() {
+            return;
+        }
         self.snake.direction = direction;
     }
+    pub fn turn(&mut self, turn: Turn) {
+        self.update_direction(self.snake.heading().turn(turn));
+    }
+    //the snake is set to go back the way it last moved
+    pub fn is_reversing(&self) -> bool {
+        self.snake.direction == self.snake.heading().opposite()
+    }
+    //the directions a learner chooses between, without the reversal unless the config
+    //allows it and actions are absolute
+    pub fn actions(&self) -> Vec<Direction> {
+        let heading = self.snake.heading();
+        match (self.config.actions, self.config.reversal) {
+            (ActionMode::Absolute, Reversal::Allowed) => Direction::ALL.to_vec(),
+            (ActionMode::Absolute, _) => Direction::ALL
+                .into_iter()
+                .filter(|direction| *direction != heading.opposite())
+                .collect(),
+            (ActionMode::Relative, _) => Turn::ALL.map(|turn| heading.turn(turn)).to_vec(),
+        }
+    }
     pub fn display(&self) {
         for row in self.board.state.iter() {
             for col in row.iter() {
@@ -202,33 +465,64 @@ impl Game {
         }
         println!("\n");
     }
+    pub fn attach(&mut self, renderer: SharedRenderer) {
+        self.renderer = Some(renderer);
+    }
+    pub fn detach(&mut self) -> Option<SharedRenderer> {
+        self.renderer.take()
+    }
+    //reseeds and resets the game, recording the episode until `stop_recording`
+    pub fn record(&mut self, seed: u64) -> Arc<Mutex<Replay>> {
+        let recording = Arc::new(Mutex::new(Replay::new(self.config.clone(), seed)));
+        self.recording = Some(recording.clone());
+        self.reseed(seed);
+        self.reset();
+        recording
+    }
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        self.recording
+            .take()
+            .map(|recording| recording.lock().unwrap().clone())
+    }
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
     pub fn reset(&mut self) {
-        self.snake = Snake::new();
-        self.apple = Apple::rand_apple(self.board.width, self.board.height);
+        self.snake = Game::spawn_snake(&self.config);
+        self.apples.clear();
+        self.board.draw(&self.snake, &self.apples);
+        self.spawn_apples();
         self.score = 0;
+        self.steps = 0;
         self.lost = false;
+        if let Some(renderer) = &self.renderer {
+            renderer.lock().unwrap().on_reset(self);
+        }
     }
     pub fn get_possible_states(&self) -> Vec<(Direction, Game)> {
         //get all possible states after one move
-        let mut states = Vec::new();
-
-        let mut game = self.clone();
-        game.update_direction(Direction::Up);
-        game.test_update();
-        states.push((Direction::Up, game));
-        game = self.clone();
-        game.update_direction(Direction::Down);
-        game.test_update();
-        states.push((Direction::Down, game));
-        game = self.clone();
-        game.update_direction(Direction::Left);
-        game.test_update();
-        states.push((Direction::Left, game));
-        game = self.clone();
-        game.update_direction(Direction::Right);
-        game.test_update();
-        states.push((Direction::Right, game));
-        states
+        self.actions()
+            .into_iter()
+            .map(|direction| {
+                let mut game = self.clone();
+                game.update_direction(direction);
+                game.test_update();
+                (direction, game)
+            })
+            .collect()
+    }
+    //the states after turning left, going straight and turning right
+    pub fn get_relative_states(&self) -> Vec<(Turn, Game)> {
+        let heading = self.snake.heading();
+        Turn::ALL
+            .into_iter()
+            .map(|turn| {
+                let mut game = self.clone();
+                game.update_direction(heading.turn(turn));
+                game.test_update();
+                (turn, game)
+            })
+            .collect()
     }
 }
 #[derive(Clone)]
@@ -244,19 +538,159 @@ impl Apple {
             },
         }
     }
-    pub fn rand_apple(width: i32, height: i32) -> Apple {
-        let mut rng = rand::thread_rng();
+    pub fn rand_apple<R: Rng + ?Sized>(width: i32, height: i32, rng: &mut R) -> Apple {
         let x = rng.gen_range(0..width);
         let y = rng.gen_range(0..height);
         Apple {
             location: Point { x, y },
         }
     }
-    pub fn from_list(list: Vec<Point>) -> Apple {
-        let mut rng = rand::thread_rng();
+    pub fn from_list<R: Rng + ?Sized>(list: Vec<Point>, rng: &mut R) -> Apple {
         let index = rng.gen_range(0..list.len());
         Apple {
-            location: list[index].clone(),
+            location: list[index],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn senses_walls_body_and_open_space() {
+        //a 5x5 board with the snake along the top row heading right
+        let config = GameConfig::new()
+            .size(5, 5)
+            .starting_position(2, 0)
+            .starting_direction(Direction::Right)
+            .starting_length(3)
+            .apple_count(0);
+        let game = Game::with_config(config);
+        assert_eq!(game.obstacle_distance(Direction::Right), 3);
+        assert_eq!(game.obstacle_distance(Direction::Up), 1);
+        assert_eq!(game.obstacle_distance(Direction::Down), 5);
+        assert_eq!(game.obstacle_distance(Direction::Left), 1);
+        assert!(!game.is_deadly(&game.next_head()));
+        assert!(game.is_deadly(&Point { x: 1, y: 0 }));
+        assert_eq!(game.reachable_cells(), 22);
+
+        //walling off the bottom rows leaves only what is left of the top two
+        let mut walled = game.clone();
+        walled.config.height = 2;
+        assert_eq!(walled.reachable_cells(), 7);
+    }
+
+    #[test]
+    fn honours_configured_board_size() {
+        let config = GameConfig::new()
+            .size(20, 8)
+            .starting_position(5, 3)
+            .starting_direction(Direction::Right)
+            .starting_length(4)
+            .apple_count(3);
+        let mut game = Game::with_config(config);
+        assert_eq!(game.board.state.len(), 8);
+        assert!(game.board.state.iter().all(|row| row.len() == 20));
+        assert_eq!(game.apples.len(), 3);
+        assert_eq!(game.snake.len(), 4);
+
+        for _ in 0..14 {
+            game.update();
+        }
+        assert!(!game.lost);
+        assert_eq!(game.snake.head().x, 19);
+        game.update();
+        assert!(game.lost);
+    }
+
+    #[test]
+    fn same_seed_spawns_same_apples() {
+        let apples = |seed| {
+            let mut game = Game::with_config(GameConfig::new().seed(seed).apple_count(5));
+            let mut locations = Vec::new();
+            for _ in 0..20 {
+                locations.extend(game.apples.iter().map(|apple| apple.location));
+                game.reset();
+            }
+            locations
+        };
+        assert_eq!(apples(7), apples(7));
+        assert_ne!(apples(7), apples(8));
+    }
+
+    #[test]
+    fn incremental_redraw_matches_full_draw() {
+        let mut game = Game::with_config(GameConfig::new().size(12, 9).apple_count(4).seed(3));
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for _ in 0..500 {
+            if game.lost {
+                game.reset();
+            }
+            game.update_direction(Direction::ALL[rng.gen_range(0..4)]);
+            game.update();
+            let mut expected = game.board.clone();
+            expected.draw(&game.snake, &game.apples);
+            assert_eq!(game.board.state, expected.state);
         }
     }
+
+    #[test]
+    fn relative_actions_and_reversals() {
+        let config = GameConfig::new()
+            .size(6, 6)
+            .starting_position(2, 2)
+            .starting_direction(Direction::Right)
+            .apple_count(0);
+        let relative = Game::with_config(config.clone().actions(ActionMode::Relative));
+        assert_eq!(
+            relative.actions(),
+            vec![Direction::Up, Direction::Right, Direction::Down]
+        );
+        let turns: Vec<Turn> = relative
+            .get_relative_states()
+            .into_iter()
+            .map(|(turn, state)| {
+                assert_eq!(state.snake.heading(), Direction::Right.turn(turn));
+                turn
+            })
+            .collect();
+        assert_eq!(turns, Turn::ALL);
+        assert_eq!(relative.get_possible_states().len(), 3);
+
+        //a single cell snake can turn straight back unless told otherwise
+        let mut allowed = Game::with_config(config.clone());
+        allowed.update_direction(Direction::Left);
+        allowed.update();
+        assert!(!allowed.lost);
+        assert_eq!(allowed.snake.head(), Point { x: 1, y: 2 });
+        assert_eq!(allowed.actions().len(), 4);
+
+        let mut ignored = Game::with_config(config.clone().reversal(Reversal::Ignored));
+        //turning twice before moving still counts as reversing
+        ignored.update_direction(Direction::Up);
+        ignored.update_direction(Direction::Left);
+        ignored.update();
+        assert!(!ignored.lost);
+        assert_eq!(ignored.snake.head(), Point { x: 2, y: 1 });
+
+        let mut disallowed = Game::with_config(config.reversal(Reversal::Disallowed));
+        assert!(!disallowed.actions().contains(&Direction::Left));
+        disallowed.turn(Turn::Left);
+        disallowed.update();
+        assert!(!disallowed.lost);
+        disallowed.update_direction(Direction::Down);
+        disallowed.update();
+        assert!(disallowed.lost);
+    }
+
+    #[test]
+    fn step_limit_ends_the_game() {
+        let mut game = Game::with_config(GameConfig::new().size(8, 8).step_limit(3));
+        game.update();
+        game.update();
+        assert!(!game.lost);
+        game.update();
+        assert!(game.lost);
+    }
 }
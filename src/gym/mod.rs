@@ -1,2 +1,6 @@
+pub mod environment;
 pub mod game;
+pub mod render;
+pub mod replay;
 pub mod snake;
+pub mod tui;
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use crate::gym::game::{Board, Game, Point};
+
+pub trait Renderer {
+    fn on_reset(&mut self, _game: &Game) {}
+    fn on_step(&mut self, _game: &Game) {}
+}
+
+pub type SharedRenderer = Arc<Mutex<dyn Renderer + Send>>;
+
+//prints the board after every step, the same way `Game::display` does
+#[derive(Debug, Clone, Default)]
+pub struct TerminalRenderer;
+
+impl Renderer for TerminalRenderer {
+    fn on_reset(&mut self, game: &Game) {
+        game.display();
+    }
+    fn on_step(&mut self, game: &Game) {
+        println!("score: {} steps: {}", game.score, game.steps);
+        game.display();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub board: Board,
+    pub head: Point,
+    pub score: u32,
+    pub steps: u32,
+    pub lost: bool,
+}
+
+//keeps a copy of every frame so episodes can be inspected afterwards
+#[derive(Debug, Clone, Default)]
+pub struct FrameCapture {
+    pub frames: Vec<Frame>,
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        FrameCapture::default()
+    }
+    fn capture(&mut self, game: &Game) {
+        self.frames.push(Frame {
+            board: game.board.clone(),
+            head: game.snake.head(),
+            score: game.score,
+            steps: game.steps,
+            lost: game.lost,
+        });
+    }
+}
+
+impl Renderer for FrameCapture {
+    fn on_reset(&mut self, game: &Game) {
+        self.capture(game);
+    }
+    fn on_step(&mut self, game: &Game) {
+        self.capture(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::game::GameConfig;
+
+    #[test]
+    fn captures_a_frame_per_step() {
+        let capture = Arc::new(Mutex::new(FrameCapture::new()));
+        let mut game = Game::with_config(GameConfig::new().step_limit(5));
+        game.attach(capture.clone());
+        game.reset();
+        while !game.lost {
+            game.update();
+        }
+        //the reset plus one frame per step
+        {
+            let frames = &capture.lock().unwrap().frames;
+            assert_eq!(frames.len(), 6);
+            assert_eq!(frames[5].steps, 5);
+            assert!(frames[5].lost);
+        }
+
+        //candidate states do not notify
+        game.reset();
+        game.get_possible_states();
+        assert_eq!(capture.lock().unwrap().frames.len(), 7);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::gym::{
+    game::{Game, GameConfig, Point},
+    render::{Frame, FrameCapture},
+    snake::Direction,
+};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+//everything needed to play an episode back: the game config, the seed the game was
+//reset with, every action taken and where each apple appeared
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub config: GameConfig,
+    pub seed: u64,
+    pub actions: Vec<Direction>,
+    pub spawns: Vec<Spawn>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spawn {
+    pub step: u32,
+    pub location: Point,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    //the re-simulated apples did not match the recorded ones
+    Desync { step: u32 },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not read replay: {}", error),
+            ReplayError::Format(error) => write!(f, "malformed replay: {}", error),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay version {} is not supported, expected {}",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Desync { step } => {
+                write!(f, "replay desynced at step {}", step)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(error: serde_json::Error) -> Self {
+        ReplayError::Format(error)
+    }
+}
+
+impl Replay {
+    pub fn new(config: GameConfig, seed: u64) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            config,
+            seed,
+            actions: Vec::new(),
+            spawns: Vec::new(),
+        }
+    }
+    pub fn record(&mut self, action: Direction) {
+        self.actions.push(action);
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, ReplayError> {
+        let replay: Replay = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+    //plays the actions back on a fresh game, returning the reset frame followed by
+    //one frame per step
+    pub fn simulate(&self) -> Result<Vec<Frame>, ReplayError> {
+        let capture = Arc::new(Mutex::new(FrameCapture::new()));
+        let mut game = Game::with_config(self.config.clone());
+        game.attach(capture.clone());
+        let recording = game.record(self.seed);
+        for action in &self.actions {
+            if game.lost {
+                break;
+            }
+            game.update_direction(*action);
+            game.update();
+        }
+        game.detach();
+        game.stop_recording();
+
+        let spawns = &recording.lock().unwrap().spawns;
+        if let Some(index) = (0..self.spawns.len().max(spawns.len()))
+            .find(|index| self.spawns.get(*index) != spawns.get(*index))
+        {
+            let step = self
+                .spawns
+                .get(index)
+                .or(spawns.get(index))
+                .map(|spawn| spawn.step)
+                .unwrap_or(0);
+            return Err(ReplayError::Desync { step });
+        }
+        let frames = std::mem::take(&mut capture.lock().unwrap().frames);
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded() -> Replay {
+        let mut game = Game::with_config(GameConfig::new().size(12, 12).apple_count(2));
+        game.record(11);
+        for action in [Direction::Down, Direction::Down, Direction::Right] {
+            for _ in 0..5 {
+                game.update_direction(action);
+                game.update();
+            }
+        }
+        game.stop_recording().unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_json_and_resimulates() {
+        let replay = recorded();
+        assert_eq!(replay.actions.len(), 15);
+        assert!(replay.spawns.len() >= 2);
+
+        let path = std::env::temp_dir().join("snake-replay-round-trip.json");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, replay);
+
+        let frames = loaded.simulate().unwrap();
+        assert_eq!(frames.len(), 16);
+        assert_eq!(frames.last().unwrap().steps, 15);
+    }
+
+    #[test]
+    fn detects_desync() {
+        let mut replay = recorded();
+        replay.seed += 1;
+        assert!(matches!(
+            replay.simulate(),
+            Err(ReplayError::Desync { step: 0 })
+        ));
+    }
+}
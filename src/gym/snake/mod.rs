@@ -1,9 +1,13 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
 use crate::gym::game::Point;
 
-use self::linked_list::LinkedList;
-pub mod linked_list;
+use self::occupancy::Occupancy;
+pub mod occupancy;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -11,55 +15,212 @@ pub enum Direction {
     Right,
 }
 
-#[derive(Clone)]
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+    //a quarter turn anticlockwise as seen on screen, the snake's own left
+    pub fn turn_left(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+    pub fn turn_right(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+    //the absolute direction `turn` takes a snake heading this way
+    pub fn turn(&self, turn: Turn) -> Direction {
+        match turn {
+            Turn::Left => self.turn_left(),
+            Turn::Straight => *self,
+            Turn::Right => self.turn_right(),
+        }
+    }
+}
+
+//a move relative to the way the snake is heading
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Turn {
+    Left,
+    Straight,
+    Right,
+}
+
+impl Turn {
+    pub const ALL: [Turn; 3] = [Turn::Left, Turn::Straight, Turn::Right];
+    //the turn that takes a snake heading `from` to `to`, none for a reversal
+    pub fn between(from: Direction, to: Direction) -> Option<Turn> {
+        Turn::ALL.into_iter().find(|turn| from.turn(*turn) == to)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Snake {
-    pub body: LinkedList<Point>,
+    body: VecDeque<Point>,
+    occupied: Occupancy,
+    growth: u32,
+    collided: bool,
+    //the way the snake last moved, which `direction` may since have been changed from
+    heading: Direction,
     pub direction: Direction,
 }
 
 impl Snake {
-    pub fn new() -> Self {
-        Snake {
-            body: LinkedList::new(Some(Box::new(Node::new(Point { x: 0, y: 0 })))),
-            direction: Direction::Down,
+    pub fn new(width: i32, height: i32) -> Self {
+        Snake::spawn(Point { x: 0, y: 0 }, Direction::Down, 1, width, height)
+    }
+    pub fn spawn(
+        head: Point,
+        direction: Direction,
+        length: usize,
+        width: i32,
+        height: i32,
+    ) -> Self {
+        let mut snake = Snake {
+            body: VecDeque::with_capacity(length),
+            occupied: Occupancy::new(width, height),
+            growth: 0,
+            collided: false,
+            heading: direction,
+            direction,
+        };
+        //lay the rest of the body out behind the head
+        let mut segment = head;
+        for _ in 0..length.max(1) {
+            snake.body.push_back(segment);
+            snake.occupied.insert(&segment);
+            match direction {
+                Direction::Up => segment.y += 1,
+                Direction::Down => segment.y -= 1,
+                Direction::Left => segment.x += 1,
+                Direction::Right => segment.x -= 1,
+            }
         }
+        snake
+    }
+    pub fn body(&self) -> &VecDeque<Point> {
+        &self.body
+    }
+    pub fn head(&self) -> Point {
+        self.body[0]
+    }
+    pub fn tail(&self) -> Point {
+        self.body[self.body.len() - 1]
+    }
+    pub fn heading(&self) -> Direction {
+        self.heading
+    }
+    pub fn len(&self) -> usize {
+        self.body.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.body.is_empty()
+    }
+    pub fn contains(&self, point: &Point) -> bool {
+        self.occupied.contains(point)
+    }
+    pub fn grow(&mut self) {
+        self.growth += 1;
     }
     pub fn slither(&mut self) {
-        let mut head = self.body.front().unwrap().clone();
+        let mut head = self.head();
         match self.direction {
             Direction::Up => head.y -= 1,
             Direction::Down => head.y += 1,
             Direction::Left => head.x -= 1,
             Direction::Right => head.x += 1,
         }
+        //the tail moves out of the way before the head moves in, unless we are growing
+        if self.growth > 0 {
+            self.growth -= 1;
+        } else {
+            let tail = self.body.pop_back().unwrap();
+            self.occupied.remove(&tail);
+        }
+        self.heading = self.direction;
+        self.collided = self.occupied.contains(&head);
         self.body.push_front(head);
-        self.body.pop_back();
+        self.occupied.insert(&head);
+    }
+
+    //whether moving the head to `next` runs into the body. the tail only counts when
+    //growing, otherwise it moves out of the way first
+    pub fn bites(&self, next: &Point) -> bool {
+        self.contains(next) && (self.growth > 0 || *next != self.tail())
     }
 
     pub fn check_self_collision(&self) -> bool {
-        let head = self.body.front().unwrap();
-        let mut current = self.body.head.as_ref().unwrap().next.as_ref();
-        while let Some(node) = current {
-            if node.value.x == (*head).x && node.value.y == (*head).y {
-                return true;
-            }
-            current = node.next.as_ref();
+        self.collided
+    }
+}
+
+impl Display for Snake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for point in &self.body {
+            write!(f, "{}, ", point)?;
         }
-        false
+        Ok(())
     }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn converts_between_turns_and_directions() {
+        for heading in Direction::ALL {
+            for turn in Turn::ALL {
+                assert_eq!(Turn::between(heading, heading.turn(turn)), Some(turn));
+            }
+            assert_eq!(Turn::between(heading, heading.opposite()), None);
+        }
+        assert_eq!(Direction::Up.turn(Turn::Left), Direction::Left);
+        assert_eq!(Direction::Up.turn(Turn::Right), Direction::Right);
+    }
 
-}
+    #[test]
+    fn chases_tail_and_collides_when_growing() {
+        let mut snake = Snake::spawn(Point { x: 3, y: 2 }, Direction::Right, 4, 10, 10);
+        assert_eq!(snake.len(), 4);
+        assert!(snake.contains(&Point { x: 0, y: 2 }));
+        assert!(!snake.contains(&Point { x: 4, y: 2 }));
 
-#[derive(Clone)]
-pub struct Node<T: Copy> {
-    pub value: T,
-    next: Option<Box<Node<T>>>,
-}
+        //moving into the cell the tail is leaving is safe
+        for direction in [Direction::Down, Direction::Left, Direction::Up] {
+            snake.direction = direction;
+            snake.slither();
+            assert!(!snake.check_self_collision());
+        }
+        assert_eq!(snake.len(), 4);
 
-impl<T: Copy> Node<T> {
-    fn new(value: T) -> Self {
-        Node { value, next: None }
+        //but not when the tail stays put because we are growing
+        let tail = snake.tail();
+        assert!(!snake.bites(&tail));
+        snake.grow();
+        assert!(snake.bites(&tail));
+        snake.direction = Direction::Right;
+        snake.slither();
+        assert_eq!(snake.len(), 5);
+        assert!(snake.check_self_collision());
     }
 }
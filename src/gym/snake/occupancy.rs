@@ -0,0 +1,41 @@
+use crate::gym::game::Point;
+
+#[derive(Debug, Clone)]
+pub struct Occupancy {
+    width: i32,
+    height: i32,
+    bits: Vec<u64>,
+}
+
+impl Occupancy {
+    pub fn new(width: i32, height: i32) -> Self {
+        let cells = (width.max(0) * height.max(0)) as usize;
+        Occupancy {
+            width,
+            height,
+            bits: vec![0; cells.div_ceil(64)],
+        }
+    }
+    fn index(&self, point: &Point) -> Option<usize> {
+        if point.x < 0 || point.x >= self.width || point.y < 0 || point.y >= self.height {
+            return None;
+        }
+        Some((point.y * self.width + point.x) as usize)
+    }
+    pub fn contains(&self, point: &Point) -> bool {
+        match self.index(point) {
+            Some(index) => self.bits[index / 64] & (1 << (index % 64)) != 0,
+            None => false,
+        }
+    }
+    pub fn insert(&mut self, point: &Point) {
+        if let Some(index) = self.index(point) {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    pub fn remove(&mut self, point: &Point) {
+        if let Some(index) = self.index(point) {
+            self.bits[index / 64] &= !(1 << (index % 64));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{self, Stdout, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    execute, queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+
+use crate::gym::game::{Board, Point};
+
+pub mod play;
+pub mod replay;
+pub mod watch;
+
+//raw mode on the alternate screen, restored when dropped so a panic does not
+//leave the terminal unusable
+pub struct Terminal {
+    pub out: Stdout,
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Terminal> {
+        let mut out = io::stdout();
+        enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(Terminal { out })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+//draws the board with a coloured head, body and apples followed by status lines
+pub fn draw<W: Write>(
+    out: &mut W,
+    board: &Board,
+    head: Point,
+    status: &[String],
+) -> io::Result<()> {
+    queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+    let border = "──".repeat(board.width as usize);
+    queue!(out, Print(format!("┌{}┐", border)))?;
+    for (y, row) in board.state.iter().enumerate() {
+        queue!(out, MoveTo(0, y as u16 + 1), Print("│"))?;
+        for (x, cell) in row.iter().enumerate() {
+            let (color, glyph) = if head.x == x as i32 && head.y == y as i32 {
+                (Color::Yellow, "██")
+            } else {
+                match cell {
+                    1 => (Color::Green, "██"),
+                    2 => (Color::Red, "()"),
+                    _ => (Color::DarkGrey, " ·"),
+                }
+            };
+            queue!(out, SetForegroundColor(color), Print(glyph), ResetColor)?;
+        }
+        queue!(out, Print("│"))?;
+    }
+    let bottom = board.height as u16 + 1;
+    queue!(out, MoveTo(0, bottom), Print(format!("└{}┘", border)))?;
+    for (line, text) in status.iter().enumerate() {
+        queue!(out, MoveTo(0, bottom + 1 + line as u16), Print(text))?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::game::Game;
+
+    #[test]
+    fn draws_status_lines() {
+        let game = Game::new();
+        let mut buffer = Vec::new();
+        draw(
+            &mut buffer,
+            &game.board,
+            game.snake.head(),
+            &["score: 0".to_string()],
+        )
+        .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.contains("score: 0"));
+        assert!(text.contains("()"));
+    }
+}
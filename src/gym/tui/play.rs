@@ -0,0 +1,100 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{draw, Terminal};
+use crate::gym::{
+    environment::Environment,
+    game::{Game, GameConfig},
+    replay::Replay,
+    snake::Direction,
+};
+
+#[derive(Debug, Clone)]
+pub struct PlayConfig {
+    pub tick: Duration,
+    //seeds every episode so the recorded replays can be re-simulated
+    pub seed: u64,
+}
+
+impl Default for PlayConfig {
+    fn default() -> Self {
+        PlayConfig {
+            tick: Duration::from_millis(150),
+            seed: 0,
+        }
+    }
+}
+
+//lets a person play with the arrow keys or wasd, space pauses, r restarts and q
+//quits, every episode played is returned as a replay
+pub fn play(game_config: &GameConfig, config: &PlayConfig) -> io::Result<Vec<Replay>> {
+    let mut terminal = Terminal::enter()?;
+    let mut seeds = ChaCha8Rng::seed_from_u64(config.seed);
+    let mut game = Game::with_config(game_config.clone());
+    let mut replays = Vec::new();
+    game.record(seeds.gen());
+    let mut paused = false;
+
+    loop {
+        let state = if game.lost {
+            "game over, r restarts"
+        } else if paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        let status = vec![
+            format!(
+                "score: {}  steps: {}  length: {}",
+                game.score,
+                game.steps,
+                game.snake.len()
+            ),
+            state.to_string(),
+            "arrows/wasd move  space pause  r restart  q quit".to_string(),
+        ];
+        draw(&mut terminal.out, &game.board, game.snake.head(), &status)?;
+
+        //the last direction pressed during a tick is the one that gets played
+        let mut direction = game.snake.direction;
+        let deadline = Instant::now() + config.tick;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !event::poll(remaining)? {
+                break;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Up | KeyCode::Char('w') => direction = Direction::Up,
+                    KeyCode::Down | KeyCode::Char('s') => direction = Direction::Down,
+                    KeyCode::Left | KeyCode::Char('a') => direction = Direction::Left,
+                    KeyCode::Right | KeyCode::Char('d') => direction = Direction::Right,
+                    KeyCode::Char(' ') => paused = !paused,
+                    KeyCode::Char('r') => {
+                        replays.extend(game.stop_recording());
+                        game.record(seeds.gen());
+                        paused = false;
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc => {
+                        replays.extend(game.stop_recording());
+                        return Ok(replays);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if !paused && !game.lost {
+            game.step(direction);
+        }
+    }
+}
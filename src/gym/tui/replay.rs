@@ -0,0 +1,74 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+
+use super::{draw, Terminal};
+use crate::gym::render::Frame;
+
+//steps through recorded frames, left and right move one step, space plays,
+//+ and - change speed, home and end jump and q quits
+pub fn view(frames: &[Frame], tick: Duration) -> io::Result<()> {
+    if frames.is_empty() {
+        return Ok(());
+    }
+    let mut terminal = Terminal::enter()?;
+    let mut tick = tick;
+    let mut index = 0;
+    let mut playing = false;
+    let last = frames.len() - 1;
+
+    loop {
+        let frame = &frames[index];
+        let status = vec![
+            format!(
+                "frame {}/{}  score: {}  steps: {}{}",
+                index,
+                last,
+                frame.score,
+                frame.steps,
+                if frame.lost { "  game over" } else { "" }
+            ),
+            format!(
+                "{}  tick: {}ms",
+                if playing { "playing" } else { "paused" },
+                tick.as_millis()
+            ),
+            "left/right step  space play  +/- speed  home/end jump  q quit".to_string(),
+        ];
+        draw(&mut terminal.out, &frame.board, frame.head, &status)?;
+
+        let deadline = Instant::now() + tick;
+        let mut moved = false;
+        while !moved {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !event::poll(remaining)? {
+                if playing && index < last {
+                    index += 1;
+                }
+                break;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                moved = true;
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Right | KeyCode::Char('l') => index = (index + 1).min(last),
+                    KeyCode::Left | KeyCode::Char('h') => index = index.saturating_sub(1),
+                    KeyCode::Home => index = 0,
+                    KeyCode::End => index = last,
+                    KeyCode::Char(' ') => playing = !playing,
+                    KeyCode::Char('+') | KeyCode::Char('=') => {
+                        tick = (tick / 2).max(Duration::from_millis(10))
+                    }
+                    KeyCode::Char('-') => tick = (tick * 2).min(Duration::from_secs(2)),
+                    _ => moved = false,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+
+use super::{draw, Terminal};
+use crate::gym::{
+    environment::{Environment, Policy},
+    game::Game,
+};
+
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    pub tick: Duration,
+    pub min_tick: Duration,
+    pub max_tick: Duration,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            tick: Duration::from_millis(150),
+            min_tick: Duration::from_millis(10),
+            max_tick: Duration::from_secs(2),
+        }
+    }
+}
+
+//lets a person watch any policy play, space pauses, n steps while paused,
+//+ and - change speed, r restarts and q quits
+pub fn watch<P: Policy<Game>>(
+    game: &mut Game,
+    policy: &mut P,
+    config: &WatchConfig,
+) -> io::Result<()> {
+    let mut terminal = Terminal::enter()?;
+    let mut tick = config.tick;
+    let mut paused = false;
+    game.reset();
+
+    loop {
+        let state = if game.lost {
+            "game over, r restarts"
+        } else if paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        let status = vec![
+            format!(
+                "score: {}  steps: {}  length: {}",
+                game.score,
+                game.steps,
+                game.snake.len()
+            ),
+            format!("{}  tick: {}ms", state, tick.as_millis()),
+            "space pause  n step  +/- speed  r restart  q quit".to_string(),
+        ];
+        draw(&mut terminal.out, &game.board, game.snake.head(), &status)?;
+
+        let mut step = !paused && !game.lost;
+        let deadline = Instant::now() + tick;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !event::poll(remaining)? {
+                break;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char(' ') => paused = !paused,
+                    KeyCode::Char('n') | KeyCode::Right if paused && !game.lost => {
+                        step = true;
+                        break;
+                    }
+                    KeyCode::Char('+') | KeyCode::Char('=') => {
+                        tick = (tick / 2).max(config.min_tick);
+                    }
+                    KeyCode::Char('-') => tick = (tick * 2).min(config.max_tick),
+                    KeyCode::Char('r') => {
+                        game.reset();
+                        step = false;
+                    }
+                    _ => {}
+                }
+                if paused {
+                    step = false;
+                    break;
+                }
+            }
+        }
+
+        if step {
+            let action = policy.act(game);
+            game.step(action);
+        }
+    }
+}
@@ -1,47 +1,199 @@
-use snake::{genetic_ai::population::Population, gym::game::Game};
+use std::{env, time::Duration};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use snake::{
+    genetic_ai::{
+        checkpoint::{CheckpointConfig, Run},
+        population::{EvolutionConfig, Population},
+        typed::Type,
+        typed_population::TypedPopulation,
+    },
+    gym::{
+        game::{Game, GameConfig, Reversal},
+        replay::Replay,
+        tui::{
+            play::{play, PlayConfig},
+            replay::view,
+            watch::{watch, WatchConfig},
+        },
+    },
+};
+
+const USAGE: &str = "usage: snake [train [generations] [checkpoint] | typed [generations] | \
+    resume <checkpoint> | dot <checkpoint> | watch [generations] | \
+    play [tick_ms] [replay_prefix] | replay <path>]";
 
 fn main() {
-    let mut game = Game::new();
-    // let mut population = Population::new(1, 1);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |index: usize, default: u64| match args.get(index) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("{}", USAGE);
+            std::process::exit(2)
+        }),
+        None => default,
+    };
+    let generations = |index: usize| number(index, 10) as u32;
+    match args.first().map(String::as_str) {
+        None | Some("train") => {
+            let run = Run::new(
+                Population::new(10, 1, 0),
+                GameConfig::default(),
+                EvolutionConfig::default(),
+                generations(1),
+            );
+            train(run, args.get(2));
+        }
+        Some("typed") => {
+            //a strongly typed tree that outputs the move itself
+            let mut population = TypedPopulation::new(10, 3, Type::Direction, 0);
+            let history = population.evolve(
+                &GameConfig::default(),
+                generations(1),
+                &EvolutionConfig::default(),
+            );
+            for stats in &history {
+                println!("{}", stats);
+            }
+            let best = population.return_best();
+            println!("{} {}", best.fitness, best.gene);
+        }
+        Some("resume") => {
+            let Some(path) = args.get(1) else {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            };
+            let run = Run::load(path).unwrap_or_else(|error| {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            });
+            train(run, Some(path));
+        }
+        Some("dot") => {
+            let Some(path) = args.get(1) else {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            };
+            let run = Run::load(path).unwrap_or_else(|error| {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            });
+            //labelled with the values it computes on the run's starting board
+            let game = Game::with_config(run.game.clone());
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let best = run.population.return_best();
+            print!("{}", best.gene.to_dot_evaluated(&game, &mut rng));
+        }
+        Some("watch") => {
+            let run = Run::new(
+                Population::new(10, 1, 0),
+                GameConfig::default(),
+                EvolutionConfig::default(),
+                generations(1),
+            );
+            let population = train(run, None);
+            let best = population.return_best();
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut game = Game::with_config(GameConfig::default());
+            watch(
+                &mut game,
+                &mut best.policy(&mut rng),
+                &WatchConfig::default(),
+            )
+            .expect("terminal error");
+        }
+        Some("play") => {
+            let config = PlayConfig {
+                tick: Duration::from_millis(number(1, 150)),
+                ..PlayConfig::default()
+            };
+            let prefix = args.get(2).map(String::as_str).unwrap_or("replay");
+            //pressing back the way the snake came does nothing, as players expect
+            let game = GameConfig::new().reversal(Reversal::Ignored);
+            let replays = play(&game, &config).expect("terminal error");
+            for (index, replay) in replays.iter().enumerate() {
+                let path = format!("{}-{}.json", prefix, index + 1);
+                replay.save(&path).expect("failed to save replay");
+                println!("saved {} steps to {}", replay.actions.len(), path);
+            }
+        }
+        Some("replay") => {
+            let Some(path) = args.get(1) else {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            };
+            let frames = Replay::load(path)
+                .and_then(|replay| replay.simulate())
+                .unwrap_or_else(|error| {
+                    eprintln!("{}: {}", path, error);
+                    std::process::exit(1);
+                });
+            view(&frames, Duration::from_millis(150)).expect("terminal error");
+        }
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
 
-    let mut population = Population::new(10, 1);
-    population.evaluate(&mut game);
+//checkpoints after every generation when given a path
+fn train(mut run: Run, checkpoint: Option<&String>) -> Population {
+    let checkpoint = checkpoint.map(|path| CheckpointConfig {
+        path: path.into(),
+        every: 1,
+    });
+    run.evolve(checkpoint.as_ref())
+        .expect("failed to save checkpoint");
+    for stats in &run.history {
+        println!("{}", stats);
+    }
+    let population = run.population;
     let best = population.return_best();
-    let board = best.final_board.as_ref().unwrap();
-    let snake = best.final_snake.as_ref().unwrap();
-    println!("{} {} {}", board, best.fitness, snake.body);
+    //a run resumed after it already finished has no final board to show
+    if let (Some(board), Some(snake)) = (&best.final_board, &best.final_snake) {
+        println!("{} {} {}", board, best.fitness, snake);
+    }
     println!("{}", best.gene);
+    println!("{}", best.gene.to_sexpr());
+    population
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use snake::genetic_ai::{Agent, InternalNode, LeafNode, Node, NodeType, Tree};
+    use snake::genetic_ai::{
+        population::EvaluationConfig, Agent, InternalNode, LeafNode, Node, NodeType, Tree,
+    };
 
     #[test]
     fn it_works() {
-        let mut new_node = Node::new(NodeType::Internal(InternalNode::Sub));
-        new_node.left = Some(Box::new(Node::new(NodeType::Leaf(LeafNode::Random))));
-        new_node.right = Some(Box::new(Node::new(NodeType::Leaf(LeafNode::AppleDistance))));
+        let new_node = Node::with_children(
+            NodeType::Internal(InternalNode::Sub),
+            vec![
+                Node::new(NodeType::Leaf(LeafNode::Random)),
+                Node::new(NodeType::Leaf(LeafNode::AppleDistance)),
+            ],
+        );
         let tree = Tree { root: new_node };
 
         let mut individuals = Vec::new();
         let custum_agent = Agent {
-            fitness: 0,
+            fitness: 0.0,
+            cases: Vec::new(),
+            episodes: Vec::new(),
             gene: tree,
             final_board: None,
             final_snake: None,
         };
         individuals.push(custum_agent);
-        let mut population = Population { individuals };
+        let mut population = Population::from_individuals(individuals, 0);
 
-        let mut game = Game::new();
-        population.evaluate(&mut game);
+        population.evaluate(&GameConfig::default(), &EvaluationConfig::default());
         let best = population.return_best();
         let board = best.final_board.as_ref().unwrap();
         let snake = best.final_snake.as_ref().unwrap();
-        println!("{} {} {}", board, best.fitness, snake.body);
+        println!("{} {} {}", board, best.fitness, snake);
         println!("{}", best.gene);
-        assert!(true)
     }
 }